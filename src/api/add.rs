@@ -1,19 +1,126 @@
+use hightorrent::{InfoHash, MagnetLink, TorrentFile};
+use serde::Serialize;
+use snafu::ResultExt;
+use tokio::time::{Instant, sleep};
+
 use std::boxed::Box;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::api::Capabilities;
 use crate::api_error::*;
 
 #[async_trait]
 /// ApiAdd is implemented by torrent API clients to add new torrents/magnets to the Bittorrent client
 pub trait ApiAdd<'a>: Send + Sync {
     async fn api_add_send(&self, add: AddBuilder<'a, AddSource>) -> Result<(), ApiError>;
+
+    /// Returns the state of a torrent in the Bittorrent client, or None when it is not found.
+    async fn api_add_state(&self, hash: &InfoHash) -> Result<Option<AddedState>, ApiError>;
+
+    /// Adds many torrents, returning a result for each of them in the same order.
+    ///
+    /// A torrent failing to be added does not stop the others, its error is in its result.
+    /// By default, torrents are added one by one. Backends which can add many torrents
+    /// in a single request should override this method.
+    async fn api_add_batch(
+        &self,
+        items: Vec<AddBuilder<'a, AddSource>>,
+    ) -> Result<Vec<BatchAddResult>, ApiError> {
+        let mut results = Vec::with_capacity(items.len());
+
+        for item in items {
+            let hash = item.source.infohash().await;
+            let result = match (item.send().await, hash) {
+                (Ok(added), hash) => BatchAddResult::Added(added.or(hash.ok().flatten())),
+                (Err(ApiError::DuplicateTorrent { .. }), Ok(Some(hash))) => {
+                    BatchAddResult::Duplicate(hash)
+                }
+                (Err(ApiError::RejectedTorrent | ApiError::DuplicateTorrent { .. }), hash) => {
+                    BatchAddResult::Rejected(hash.ok().flatten())
+                }
+                // Previous torrents were already added, so keep going
+                (Err(e), _) => BatchAddResult::Failed(Arc::new(e)),
+            };
+            results.push(result);
+        }
+
+        Ok(results)
+    }
 }
 
+/// Forwards to a backend which may be unsized, such as `dyn Api`, so generic code can give it
+/// to an [AddBuilder]
+pub(crate) struct AddRef<'r, A: ?Sized>(pub(crate) &'r A);
+
+#[async_trait]
+impl<'a, A: ApiAdd<'a> + ?Sized> ApiAdd<'a> for AddRef<'_, A> {
+    async fn api_add_send(&self, add: AddBuilder<'a, AddSource>) -> Result<(), ApiError> {
+        self.0.api_add_send(add).await
+    }
+
+    async fn api_add_state(&self, hash: &InfoHash) -> Result<Option<AddedState>, ApiError> {
+        self.0.api_add_state(hash).await
+    }
+
+    async fn api_add_batch(
+        &self,
+        items: Vec<AddBuilder<'a, AddSource>>,
+    ) -> Result<Vec<BatchAddResult>, ApiError> {
+        self.0.api_add_batch(items).await
+    }
+}
+
+/// The outcome of adding a single torrent as part of a batch
+#[derive(Debug)]
+pub enum BatchAddResult {
+    /// The torrent was added. The infohash is None for URLs.
+    Added(Option<InfoHash>),
+    /// The Bittorrent client already had this torrent
+    Duplicate(InfoHash),
+    /// The Bittorrent client rejected the torrent as invalid
+    Rejected(Option<InfoHash>),
+    /// The Bittorrent client accepted the torrent, but it did not appear in its list of torrents
+    /// before the check timed out. It may still be added later.
+    NotYetVisible(InfoHash),
+    /// The torrent could not be read or sent, or the request failed. The error is shared by all
+    /// the torrents sent in the same request.
+    Failed(Arc<ApiError>),
+}
+
+/// The state of a newly-added torrent in the Bittorrent client
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddedState {
+    /// The torrent was added from a magnet, and its metadata was not fetched yet
+    MissingMetadata,
+    /// The torrent has its metadata
+    Ready,
+}
+
+/// How long to wait for a torrent to appear in the Bittorrent client after adding it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddWait {
+    pub timeout: Duration,
+    /// Also wait for the metadata to be fetched, for magnets
+    pub metadata: bool,
+}
+
+/// How often the Bittorrent client is polled when waiting for a torrent
+pub(crate) const ADD_WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
 pub struct NoAddSource;
+#[derive(Clone)]
 pub enum AddSource {
     MagnetStr(String),
     MagnetFile(PathBuf),
     TorrentFile(PathBuf),
+    /// Raw .torrent file contents
+    TorrentBytes(Vec<u8>),
+    /// Already-parsed .torrent file
+    TorrentParsed(Box<TorrentFile>),
+    /// HTTP(S) URL to a .torrent file, fetched by the torrent client
+    Url(String),
 }
 
 impl AddSource {
@@ -28,6 +135,59 @@ impl AddSource {
     pub fn torrent_file(p: &Path) -> AddSource {
         AddSource::TorrentFile(p.to_path_buf())
     }
+
+    pub fn torrent_bytes(b: Vec<u8>) -> AddSource {
+        AddSource::TorrentBytes(b)
+    }
+
+    pub fn torrent_parsed(t: TorrentFile) -> AddSource {
+        AddSource::TorrentParsed(Box::new(t))
+    }
+
+    pub fn url(u: &str) -> AddSource {
+        AddSource::Url(u.to_string())
+    }
+
+    /// Computes the infohash of the torrent locally.
+    ///
+    /// Returns None for URLs, which are only downloaded by the Bittorrent client.
+    pub async fn infohash(&self) -> Result<Option<InfoHash>, ApiError> {
+        match self {
+            AddSource::MagnetStr(magnet) => Ok(Some(magnet_infohash(magnet)?)),
+            AddSource::MagnetFile(path) => {
+                let magnet =
+                    tokio::fs::read_to_string(path)
+                        .await
+                        .context(FailedReadTorrentError {
+                            path: path.to_path_buf(),
+                        })?;
+                Ok(Some(magnet_infohash(magnet.trim())?))
+            }
+            AddSource::TorrentFile(path) => {
+                let bytes = tokio::fs::read(path)
+                    .await
+                    .context(FailedReadTorrentError {
+                        path: path.to_path_buf(),
+                    })?;
+                Ok(Some(torrent_infohash(&bytes)?))
+            }
+            AddSource::TorrentBytes(bytes) => Ok(Some(torrent_infohash(bytes)?)),
+            AddSource::TorrentParsed(torrent) => Ok(Some(torrent.hash.clone())),
+            AddSource::Url(_) => Ok(None),
+        }
+    }
+}
+
+pub(crate) fn magnet_infohash(magnet: &str) -> Result<InfoHash, ApiError> {
+    MagnetLink::new(magnet)
+        .context(InvalidMagnetError)
+        .map(|magnet| magnet.hash().clone())
+}
+
+pub(crate) fn torrent_infohash(bytes: &[u8]) -> Result<InfoHash, ApiError> {
+    TorrentFile::from_slice(bytes)
+        .context(InvalidTorrentError)
+        .map(|torrent| torrent.hash)
 }
 
 pub struct AddBuilder<'a, T> {
@@ -38,6 +198,23 @@ pub struct AddBuilder<'a, T> {
     pub save_path: Option<String>,
     pub paused: Option<bool>,
     pub tags: Option<Vec<String>>,
+    pub category: Option<String>,
+    pub rename: Option<String>,
+    pub skip_checking: Option<bool>,
+    pub content_layout: Option<ContentLayout>,
+    pub stop_condition: Option<StopCondition>,
+    pub auto_management: Option<bool>,
+    pub download_path: Option<String>,
+    /// Upload limit in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Download limit in bytes per second
+    pub download_limit: Option<u64>,
+    pub ratio_limit: Option<SeedingLimit<f64>>,
+    pub seeding_time_limit: Option<SeedingLimit<Duration>>,
+    pub sequential_download: Option<bool>,
+    pub first_last_piece_priority: Option<bool>,
+    pub add_to_top_of_queue: Option<bool>,
+    pub wait: Option<AddWait>,
 }
 
 impl<'a> AddBuilder<'a, NoAddSource> {
@@ -51,64 +228,91 @@ impl<'a> AddBuilder<'a, NoAddSource> {
             save_path: None,
             paused: None,
             tags: None,
+            category: None,
+            rename: None,
+            skip_checking: None,
+            content_layout: None,
+            stop_condition: None,
+            auto_management: None,
+            download_path: None,
+            upload_limit: None,
+            download_limit: None,
+            ratio_limit: None,
+            seeding_time_limit: None,
+            sequential_download: None,
+            first_last_piece_priority: None,
+            add_to_top_of_queue: None,
+            wait: None,
         }
     }
 }
 
 impl<'a> AddBuilder<'a, NoAddSource> {
     pub fn magnet(self, s: &'a str) -> AddBuilder<'a, AddSource> {
-        let Self {
-            api,
-            save_path,
-            paused,
-            tags,
-            ..
-        } = self;
-        AddBuilder {
-            api,
-            source: AddSource::magnet(s),
-            save_path,
-            paused,
-            tags,
-        }
+        self.with_source(AddSource::magnet(s))
     }
 
     pub fn magnet_file(self, s: &Path) -> AddBuilder<'a, AddSource> {
-        let Self {
-            api,
-            save_path,
-            paused,
-            tags,
-            ..
-        } = self;
-        AddBuilder {
-            api,
-            source: AddSource::magnet_file(s),
-            save_path,
-            paused,
-            tags,
-        }
+        self.with_source(AddSource::magnet_file(s))
     }
 
     pub fn torrent_file(self, s: &Path) -> AddBuilder<'a, AddSource> {
-        let Self {
-            api,
-            save_path,
-            paused,
-            tags,
-            ..
-        } = self;
-        AddBuilder {
-            api,
-            source: AddSource::torrent_file(s),
-            save_path,
-            paused,
-            tags,
-        }
+        self.with_source(AddSource::torrent_file(s))
+    }
+
+    /// Add a torrent from any source
+    pub fn source(self, source: AddSource) -> AddBuilder<'a, AddSource> {
+        self.with_source(source)
+    }
+
+    /// Add a torrent from the raw contents of a .torrent file
+    pub fn torrent_bytes(self, b: Vec<u8>) -> AddBuilder<'a, AddSource> {
+        self.with_source(AddSource::torrent_bytes(b))
+    }
+
+    /// Add a torrent from an already-parsed .torrent file
+    pub fn torrent_parsed(self, t: TorrentFile) -> AddBuilder<'a, AddSource> {
+        self.with_source(AddSource::torrent_parsed(t))
+    }
+
+    /// Add a torrent from an HTTP(S) URL, which the torrent client will download itself
+    pub fn url(self, u: &str) -> AddBuilder<'a, AddSource> {
+        self.with_source(AddSource::url(u))
+    }
+
+    /// Add many torrents at once, sharing the options from this builder
+    pub fn batch(self, sources: Vec<AddSource>) -> AddBuilder<'a, Vec<AddSource>> {
+        self.with_source(sources)
     }
 }
 
 impl<'a, S> AddBuilder<'a, S> {
+    /// Returns a new builder with the same options, for another torrent source
+    fn with_source<T>(&self, source: T) -> AddBuilder<'a, T> {
+        AddBuilder {
+            api: self.api,
+            source,
+            save_path: self.save_path.clone(),
+            paused: self.paused,
+            tags: self.tags.clone(),
+            category: self.category.clone(),
+            rename: self.rename.clone(),
+            skip_checking: self.skip_checking,
+            content_layout: self.content_layout,
+            stop_condition: self.stop_condition,
+            auto_management: self.auto_management,
+            download_path: self.download_path.clone(),
+            upload_limit: self.upload_limit,
+            download_limit: self.download_limit,
+            ratio_limit: self.ratio_limit,
+            seeding_time_limit: self.seeding_time_limit,
+            sequential_download: self.sequential_download,
+            first_last_piece_priority: self.first_last_piece_priority,
+            add_to_top_of_queue: self.add_to_top_of_queue,
+            wait: self.wait,
+        }
+    }
+
     pub fn paused(mut self, p: bool) -> AddBuilder<'a, S> {
         self.paused = Some(p);
         self
@@ -123,10 +327,351 @@ impl<'a, S> AddBuilder<'a, S> {
         self.save_path = Some(s.to_string());
         self
     }
+
+    pub fn category(mut self, c: &str) -> AddBuilder<'a, S> {
+        self.category = Some(c.to_string());
+        self
+    }
+
+    /// Rename the torrent after adding it
+    pub fn rename(mut self, n: &str) -> AddBuilder<'a, S> {
+        self.rename = Some(n.to_string());
+        self
+    }
+
+    /// Skip checking existing files against the torrent pieces
+    pub fn skip_checking(mut self, s: bool) -> AddBuilder<'a, S> {
+        self.skip_checking = Some(s);
+        self
+    }
+
+    pub fn content_layout(mut self, l: ContentLayout) -> AddBuilder<'a, S> {
+        self.content_layout = Some(l);
+        self
+    }
+
+    pub fn stop_condition(mut self, c: StopCondition) -> AddBuilder<'a, S> {
+        self.stop_condition = Some(c);
+        self
+    }
+
+    /// Enable automatic torrent management (save path derived from category)
+    pub fn auto_management(mut self, a: bool) -> AddBuilder<'a, S> {
+        self.auto_management = Some(a);
+        self
+    }
+
+    /// Path where incomplete torrents are stored, before being moved to the save path
+    pub fn download_path(mut self, s: &str) -> AddBuilder<'a, S> {
+        self.download_path = Some(s.to_string());
+        self
+    }
+
+    /// Upload limit in bytes per second
+    pub fn upload_limit(mut self, l: u64) -> AddBuilder<'a, S> {
+        self.upload_limit = Some(l);
+        self
+    }
+
+    /// Download limit in bytes per second
+    pub fn download_limit(mut self, l: u64) -> AddBuilder<'a, S> {
+        self.download_limit = Some(l);
+        self
+    }
+
+    /// Ratio limit, which must be a positive number when it's a `SeedingLimit::Limit`
+    pub fn ratio_limit(mut self, r: SeedingLimit<f64>) -> AddBuilder<'a, S> {
+        self.ratio_limit = Some(r);
+        self
+    }
+
+    /// Seeding time limit. Backends counting in minutes round it up to the next minute.
+    pub fn seeding_time_limit(mut self, d: SeedingLimit<Duration>) -> AddBuilder<'a, S> {
+        self.seeding_time_limit = Some(d);
+        self
+    }
+
+    pub fn sequential_download(mut self, s: bool) -> AddBuilder<'a, S> {
+        self.sequential_download = Some(s);
+        self
+    }
+
+    pub fn first_last_piece_priority(mut self, f: bool) -> AddBuilder<'a, S> {
+        self.first_last_piece_priority = Some(f);
+        self
+    }
+
+    pub fn add_to_top_of_queue(mut self, t: bool) -> AddBuilder<'a, S> {
+        self.add_to_top_of_queue = Some(t);
+        self
+    }
+
+    /// Wait until the torrent is visible in the Bittorrent client, failing after `timeout`.
+    ///
+    /// Not supported for torrents added from a URL, which fail with `ApiError::UnsupportedWait`.
+    pub fn wait(mut self, timeout: Duration) -> AddBuilder<'a, S> {
+        self.wait = Some(AddWait {
+            timeout,
+            metadata: false,
+        });
+        self
+    }
+
+    /// Wait until the torrent's metadata is fetched by the Bittorrent client, failing after `timeout`.
+    ///
+    /// Not supported for torrents added from a URL, which fail with `ApiError::UnsupportedWait`.
+    pub fn wait_metadata(mut self, timeout: Duration) -> AddBuilder<'a, S> {
+        self.wait = Some(AddWait {
+            timeout,
+            metadata: true,
+        });
+        self
+    }
+
+    /// Returns the list of options which were set on this builder
+    pub fn requested_options(&self) -> Vec<AddOption> {
+        let set = [
+            (AddOption::SavePath, self.save_path.is_some()),
+            (AddOption::Paused, self.paused.is_some()),
+            (AddOption::Tags, self.tags.is_some()),
+            (AddOption::Category, self.category.is_some()),
+            (AddOption::Rename, self.rename.is_some()),
+            (AddOption::SkipChecking, self.skip_checking.is_some()),
+            (AddOption::ContentLayout, self.content_layout.is_some()),
+            (AddOption::StopCondition, self.stop_condition.is_some()),
+            (AddOption::AutoManagement, self.auto_management.is_some()),
+            (AddOption::DownloadPath, self.download_path.is_some()),
+            (AddOption::UploadLimit, self.upload_limit.is_some()),
+            (AddOption::DownloadLimit, self.download_limit.is_some()),
+            (AddOption::RatioLimit, self.ratio_limit.is_some()),
+            (
+                AddOption::SeedingTimeLimit,
+                self.seeding_time_limit.is_some(),
+            ),
+            (
+                AddOption::SequentialDownload,
+                self.sequential_download.is_some(),
+            ),
+            (
+                AddOption::FirstLastPiecePriority,
+                self.first_last_piece_priority.is_some(),
+            ),
+            (
+                AddOption::AddToTopOfQueue,
+                self.add_to_top_of_queue.is_some(),
+            ),
+        ];
+
+        set.into_iter()
+            .filter_map(|(option, is_set)| is_set.then_some(option))
+            .collect()
+    }
+
+    /// Ensures all requested options are valid and supported by the backend.
+    ///
+    /// Used by backends to report options they cannot honour, instead of silently ignoring them.
+    pub fn ensure_supported(&self, capabilities: &Capabilities) -> Result<(), ApiError> {
+        if let Some(SeedingLimit::Limit(ratio)) = self.ratio_limit
+            && !(ratio.is_finite() && ratio >= 0.0)
+        {
+            return Err(ApiError::InvalidAddOption {
+                option: AddOption::RatioLimit,
+                value: ratio.to_string(),
+            });
+        }
+
+        match self
+            .requested_options()
+            .into_iter()
+            .find(|option| !capabilities.supports_add_option(*option))
+        {
+            Some(option) => Err(ApiError::UnsupportedAddOption { option }),
+            None => Ok(()),
+        }
+    }
 }
 
 impl AddBuilder<'_, AddSource> {
-    pub async fn send(self) -> Result<(), ApiError> {
-        self.api.api_add_send(self).await
+    /// Adds the torrent, returning its infohash.
+    ///
+    /// The infohash is computed locally from the torrent/magnet, so it's None only for
+    /// [AddSource::Url](AddSource::Url). When the Bittorrent client refuses the torrent because
+    /// it already has it, `ApiError::DuplicateTorrent` is returned.
+    pub async fn send(self) -> Result<Option<InfoHash>, ApiError> {
+        let api = self.api;
+        let wait = self.wait;
+        // The torrent can't be found without its infohash, so fail before adding it
+        if let (Some(_), AddSource::Url(url)) = (wait, &self.source) {
+            return Err(ApiError::UnsupportedWait { url: url.clone() });
+        }
+        // Torrent files are streamed to the Bittorrent client, and only read again once
+        // uploaded to compute their infohash, so they are never in memory during the upload
+        let (hash, deferred) = match &self.source {
+            AddSource::TorrentFile(_) => (None, Some(self.source.clone())),
+            source => (source.infohash().await?, None),
+        };
+
+        let result = api.api_add_send(self).await;
+        let hash = match (&result, deferred) {
+            (Ok(()) | Err(ApiError::RejectedTorrent), Some(source)) => source.infohash().await?,
+            _ => hash,
+        };
+
+        match (result, &hash) {
+            (Ok(()), _) => {}
+            (Err(ApiError::RejectedTorrent), Some(hash)) => {
+                if api.api_add_state(hash).await?.is_some() {
+                    return Err(ApiError::DuplicateTorrent {
+                        hash: hash.to_string(),
+                    });
+                } else {
+                    return Err(ApiError::RejectedTorrent);
+                }
+            }
+            (Err(e), _) => return Err(e),
+        }
+
+        if let (Some(wait), Some(hash)) = (wait, &hash) {
+            wait_added(api, hash, wait).await?;
+        }
+
+        Ok(hash)
+    }
+}
+
+impl AddBuilder<'_, Vec<AddSource>> {
+    /// Adds all torrents, returning a result for each of them in the same order.
+    pub async fn send(self) -> Result<Vec<BatchAddResult>, ApiError> {
+        let items = self
+            .source
+            .iter()
+            .map(|source| self.with_source(source.clone()))
+            .collect();
+        self.api.api_add_batch(items).await
+    }
+}
+
+/// Polls the Bittorrent client until the torrent appears, or the timeout is reached
+pub(crate) async fn wait_added(
+    api: &dyn ApiAdd<'_>,
+    hash: &InfoHash,
+    wait: AddWait,
+) -> Result<(), ApiError> {
+    let deadline = Instant::now() + wait.timeout;
+
+    loop {
+        match api.api_add_state(hash).await? {
+            Some(AddedState::Ready) => return Ok(()),
+            Some(AddedState::MissingMetadata) if !wait.metadata => return Ok(()),
+            _ => {}
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(ApiError::AddTimeout {
+                hash: hash.to_string(),
+            });
+        }
+        sleep(ADD_WAIT_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+/// How the files of a torrent are laid out in the save path
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentLayout {
+    /// Keep the layout from the torrent
+    Original,
+    /// Always create a subfolder named after the torrent
+    Subfolder,
+    /// Never create a subfolder
+    NoSubfolder,
+}
+
+/// When to stop a torrent automatically after adding it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopCondition {
+    None,
+    MetadataReceived,
+    FilesChecked,
+}
+
+/// A ratio or seeding time limit, after which a torrent stops seeding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedingLimit<T> {
+    /// Use the global limit of the Bittorrent client
+    Global,
+    /// Seed forever
+    Unlimited,
+    Limit(T),
+}
+
+/// An option from the [AddBuilder](AddBuilder), used by backends to report unsupported options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddOption {
+    SavePath,
+    Paused,
+    Tags,
+    Category,
+    Rename,
+    SkipChecking,
+    ContentLayout,
+    StopCondition,
+    AutoManagement,
+    DownloadPath,
+    UploadLimit,
+    DownloadLimit,
+    RatioLimit,
+    SeedingTimeLimit,
+    SequentialDownload,
+    FirstLastPiecePriority,
+    AddToTopOfQueue,
+}
+
+impl AddOption {
+    /// All the options supported by the [AddBuilder](AddBuilder)
+    pub const ALL: &'static [AddOption] = &[
+        AddOption::SavePath,
+        AddOption::Paused,
+        AddOption::Tags,
+        AddOption::Category,
+        AddOption::Rename,
+        AddOption::SkipChecking,
+        AddOption::ContentLayout,
+        AddOption::StopCondition,
+        AddOption::AutoManagement,
+        AddOption::DownloadPath,
+        AddOption::UploadLimit,
+        AddOption::DownloadLimit,
+        AddOption::RatioLimit,
+        AddOption::SeedingTimeLimit,
+        AddOption::SequentialDownload,
+        AddOption::FirstLastPiecePriority,
+        AddOption::AddToTopOfQueue,
+    ];
+}
+
+impl std::fmt::Display for AddOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            AddOption::SavePath => "save_path",
+            AddOption::Paused => "paused",
+            AddOption::Tags => "tags",
+            AddOption::Category => "category",
+            AddOption::Rename => "rename",
+            AddOption::SkipChecking => "skip_checking",
+            AddOption::ContentLayout => "content_layout",
+            AddOption::StopCondition => "stop_condition",
+            AddOption::AutoManagement => "auto_management",
+            AddOption::DownloadPath => "download_path",
+            AddOption::UploadLimit => "upload_limit",
+            AddOption::DownloadLimit => "download_limit",
+            AddOption::RatioLimit => "ratio_limit",
+            AddOption::SeedingTimeLimit => "seeding_time_limit",
+            AddOption::SequentialDownload => "sequential_download",
+            AddOption::FirstLastPiecePriority => "first_last_piece_priority",
+            AddOption::AddToTopOfQueue => "add_to_top_of_queue",
+        };
+        write!(f, "{name}")
     }
 }
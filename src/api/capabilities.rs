@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+use std::collections::HashSet;
+
+use crate::api::AddOption;
+
+/// An operation of the [Api](crate::Api) trait, which a backend may not support
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    List,
+    Get,
+    Remove,
+    GetTrackers,
+    GetTrackerDetails,
+    AddTracker,
+    AddTrackerTier,
+    RemoveTracker,
+    EditTracker,
+    GetFiles,
+    GetSettings,
+    Export,
+    AddMagnet,
+    AddTorrent,
+    AddUrl,
+}
+
+impl Operation {
+    /// All the operations of the [Api](crate::Api) trait
+    pub const ALL: &'static [Operation] = &[
+        Operation::List,
+        Operation::Get,
+        Operation::Remove,
+        Operation::GetTrackers,
+        Operation::GetTrackerDetails,
+        Operation::AddTracker,
+        Operation::AddTrackerTier,
+        Operation::RemoveTracker,
+        Operation::EditTracker,
+        Operation::GetFiles,
+        Operation::GetSettings,
+        Operation::Export,
+        Operation::AddMagnet,
+        Operation::AddTorrent,
+        Operation::AddUrl,
+    ];
+
+    /// The operations every backend implements, because the [Api](crate::Api) trait has no
+    /// default for them
+    pub const REQUIRED: &'static [Operation] = &[
+        Operation::List,
+        Operation::Get,
+        Operation::Remove,
+        Operation::GetTrackers,
+        Operation::AddTracker,
+        Operation::RemoveTracker,
+        Operation::GetFiles,
+        Operation::AddMagnet,
+        Operation::AddTorrent,
+        Operation::AddUrl,
+    ];
+}
+
+/// A version of the Bittorrent protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    V1,
+    V2,
+    /// Torrents with both v1 and v2 metadata
+    Hybrid,
+}
+
+impl Protocol {
+    pub const ALL: &'static [Protocol] = &[Protocol::V1, Protocol::V2, Protocol::Hybrid];
+}
+
+/// What a backend supports, as returned by [Api::capabilities](crate::Api::capabilities)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub operations: HashSet<Operation>,
+    pub add_options: HashSet<AddOption>,
+    pub protocols: HashSet<Protocol>,
+}
+
+impl Capabilities {
+    /// Capabilities of a backend supporting everything
+    pub fn all() -> Capabilities {
+        Capabilities {
+            operations: Operation::ALL.iter().copied().collect(),
+            add_options: AddOption::ALL.iter().copied().collect(),
+            protocols: Protocol::ALL.iter().copied().collect(),
+        }
+    }
+
+    /// Capabilities of a backend only implementing the required operations of the
+    /// [Api](crate::Api) trait, with all add options, for v1 torrents
+    pub fn required() -> Capabilities {
+        Capabilities {
+            operations: Operation::REQUIRED.iter().copied().collect(),
+            add_options: AddOption::ALL.iter().copied().collect(),
+            protocols: HashSet::from([Protocol::V1]),
+        }
+    }
+
+    pub fn supports(&self, operation: Operation) -> bool {
+        self.operations.contains(&operation)
+    }
+
+    pub fn supports_add_option(&self, option: AddOption) -> bool {
+        self.add_options.contains(&option)
+    }
+
+    pub fn supports_protocol(&self, protocol: Protocol) -> bool {
+        self.protocols.contains(&protocol)
+    }
+}
@@ -0,0 +1,234 @@
+use hightorrent::{
+    InfoHash, MultiTarget, SingleTarget, Torrent, TorrentContent, TorrentFile, TorrentList, Tracker,
+};
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::ApiError;
+use crate::api::*;
+
+/// The torrent client software behind an [Api](crate::Api) backend
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    QBittorrent,
+}
+
+impl FromStr for Backend {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Backend, ApiError> {
+        match s.to_lowercase().as_str() {
+            "qbittorrent" => Ok(Backend::QBittorrent),
+            _ => Err(ApiError::UnsupportedBackend {
+                backend: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl Backend {
+    /// Splits a backend URL into the backend, and the HTTP(S) URL to reach it.
+    ///
+    /// The backend is selected by the URL scheme: `qbittorrent+https://host` and
+    /// `qbittorrent+http://host` select qBittorrent over HTTPS/HTTP, and `qbittorrent://host`
+    /// is a shorthand for the latter. Plain `http://` and `https://` URLs select the default
+    /// backend.
+    pub fn from_url(url: &str) -> Result<(Backend, String), ApiError> {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return Err(ApiError::UnsupportedBackend {
+                backend: url.to_string(),
+            });
+        };
+
+        let (backend, transport) = match scheme.split_once('+') {
+            Some((backend, transport)) => (backend.parse()?, transport),
+            None if scheme == "http" || scheme == "https" => (Backend::default(), scheme),
+            None => (scheme.parse()?, "http"),
+        };
+
+        match transport {
+            "http" | "https" => Ok((backend, format!("{transport}://{rest}"))),
+            _ => Err(ApiError::UnsupportedBackend {
+                backend: scheme.to_string(),
+            }),
+        }
+    }
+
+    /// Logs into this backend
+    #[cfg_attr(not(feature = "qbittorrent"), allow(unused_variables))]
+    pub async fn login(
+        &self,
+        host: &str,
+        user: &str,
+        password: &str,
+    ) -> Result<Box<dyn Api>, ApiError> {
+        match self {
+            #[cfg(feature = "qbittorrent")]
+            Backend::QBittorrent => Ok(Box::new(
+                crate::QBittorrentClient::login(host, user, password).await?,
+            )),
+            #[cfg(not(feature = "qbittorrent"))]
+            Backend::QBittorrent => Err(ApiError::UnsupportedBackend {
+                backend: "qbittorrent".to_string(),
+            }),
+        }
+    }
+}
+
+/// Logs into the backend selected by the URL scheme, such as `qbittorrent+https://`.
+///
+/// See [Backend::from_url] for the supported schemes.
+pub async fn connect(url: &str, user: &str, password: &str) -> Result<Box<dyn Api>, ApiError> {
+    let (backend, host) = Backend::from_url(url)?;
+    backend.login(&host, user, password).await
+}
+
+impl dyn Api {
+    /// Adds a torrent through a backend selected at runtime
+    pub fn add(&self) -> AddBuilder<'_, NoAddSource> {
+        AddBuilder::new(self)
+    }
+}
+
+/// Implements [Api] and [ApiAdd] for a smart pointer to `dyn Api`, so it can be used
+/// wherever a concrete backend is expected. `login` uses the URL scheme to select the backend.
+macro_rules! impl_dyn_api {
+    ($pointer:ident) => {
+        #[async_trait]
+        impl<'a> ApiAdd<'a> for $pointer<dyn Api> {
+            async fn api_add_send(&self, add: AddBuilder<'a, AddSource>) -> Result<(), ApiError> {
+                (**self).api_add_send(add).await
+            }
+
+            async fn api_add_state(&self, hash: &InfoHash) -> Result<Option<AddedState>, ApiError> {
+                (**self).api_add_state(hash).await
+            }
+
+            async fn api_add_batch(
+                &self,
+                items: Vec<AddBuilder<'a, AddSource>>,
+            ) -> Result<Vec<BatchAddResult>, ApiError> {
+                (**self).api_add_batch(items).await
+            }
+        }
+
+        #[async_trait]
+        impl Api for $pointer<dyn Api> {
+            async fn login(host: &str, user: &str, password: &str) -> Result<Self, ApiError> {
+                Ok($pointer::from(connect(host, user, password).await?))
+            }
+
+            fn host(&self) -> String {
+                (**self).host()
+            }
+
+            fn user(&self) -> String {
+                (**self).user()
+            }
+
+            fn password(&self) -> String {
+                (**self).password()
+            }
+
+            async fn capabilities(&self) -> Result<Capabilities, ApiError> {
+                (**self).capabilities().await
+            }
+
+            async fn list(&self) -> Result<TorrentList, ApiError> {
+                (**self).list().await
+            }
+
+            async fn get(&self, hash: &SingleTarget) -> Result<Option<Torrent>, ApiError> {
+                (**self).get(hash).await
+            }
+
+            async fn remove(
+                &self,
+                hash: &SingleTarget,
+                delete_files: bool,
+            ) -> Result<(), ApiError> {
+                (**self).remove(hash, delete_files).await
+            }
+
+            async fn get_trackers(&self, hash: &SingleTarget) -> Result<Vec<Tracker>, ApiError> {
+                (**self).get_trackers(hash).await
+            }
+
+            async fn add_tracker(
+                &self,
+                hash: &SingleTarget,
+                tracker: &str,
+            ) -> Result<(), ApiError> {
+                (**self).add_tracker(hash, tracker).await
+            }
+
+            async fn add_tracker_tier(
+                &self,
+                hash: &SingleTarget,
+                tracker: &str,
+                tier: u32,
+            ) -> Result<(), ApiError> {
+                (**self).add_tracker_tier(hash, tracker, tier).await
+            }
+
+            async fn remove_tracker(
+                &self,
+                hash: &SingleTarget,
+                tracker: &str,
+            ) -> Result<(), ApiError> {
+                (**self).remove_tracker(hash, tracker).await
+            }
+
+            async fn edit_tracker(
+                &self,
+                hash: &SingleTarget,
+                from: &str,
+                to: &str,
+            ) -> Result<(), ApiError> {
+                (**self).edit_tracker(hash, from, to).await
+            }
+
+            async fn get_tracker_details(
+                &self,
+                hash: &SingleTarget,
+            ) -> Result<TrackerDetails, ApiError> {
+                (**self).get_tracker_details(hash).await
+            }
+
+            async fn get_files(
+                &self,
+                hash: &SingleTarget,
+            ) -> Result<Vec<TorrentContent>, ApiError> {
+                (**self).get_files(hash).await
+            }
+
+            async fn get_settings(&self, hash: &SingleTarget) -> Result<TorrentSettings, ApiError> {
+                (**self).get_settings(hash).await
+            }
+
+            async fn export(&self, hash: &SingleTarget) -> Result<Vec<u8>, ApiError> {
+                (**self).export(hash).await
+            }
+
+            async fn export_parsed(&self, hash: &SingleTarget) -> Result<TorrentFile, ApiError> {
+                (**self).export_parsed(hash).await
+            }
+
+            async fn export_to_dir(
+                &self,
+                target: &MultiTarget,
+                dir: &Path,
+            ) -> Result<Vec<PathBuf>, ApiError> {
+                (**self).export_to_dir(target, dir).await
+            }
+        }
+    };
+}
+
+impl_dyn_api!(Box);
+impl_dyn_api!(Arc);
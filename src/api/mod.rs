@@ -1,9 +1,27 @@
 use crate::ApiError;
-use hightorrent::{SingleTarget, Torrent, TorrentContent, TorrentList, Tracker};
+use crate::api_error::*;
+use hightorrent::{
+    MultiTarget, SingleTarget, Torrent, TorrentContent, TorrentFile, TorrentList, Tracker,
+};
+use snafu::ResultExt;
+
+use std::path::{Path, PathBuf};
 
 mod add;
 pub use add::*;
 
+mod capabilities;
+pub use capabilities::{Capabilities, Operation, Protocol};
+
+mod dynamic;
+pub use dynamic::{Backend, connect};
+
+mod settings;
+pub use settings::TorrentSettings;
+
+mod tracker;
+pub use tracker::{TrackerDetails, TrackerEntry, TrackerStatus};
+
 #[async_trait]
 pub trait Api: Send + Sync + for<'a> ApiAdd<'a> {
     // Build Api client
@@ -16,6 +34,14 @@ pub trait Api: Send + Sync + for<'a> ApiAdd<'a> {
     fn user(&self) -> String;
     fn password(&self) -> String;
 
+    /// Returns the operations, add options and Bittorrent protocols supported by the backend.
+    ///
+    /// By default, only the required operations are reported, so backends implementing the
+    /// optional ones should override this method.
+    async fn capabilities(&self) -> Result<Capabilities, ApiError> {
+        Ok(Capabilities::required())
+    }
+
     // Torrent information
     async fn list(&self) -> Result<TorrentList, ApiError>;
     async fn get(&self, hash: &SingleTarget) -> Result<Option<Torrent>, ApiError>;
@@ -26,6 +52,97 @@ pub trait Api: Send + Sync + for<'a> ApiAdd<'a> {
     async fn get_trackers(&self, hash: &SingleTarget) -> Result<Vec<Tracker>, ApiError>;
     async fn add_tracker(&self, hash: &SingleTarget, tracker: &str) -> Result<(), ApiError>;
     async fn remove_tracker(&self, hash: &SingleTarget, tracker: &str) -> Result<(), ApiError>;
+    /// Adds a tracker to a torrent in the given tier, starting at 0.
+    ///
+    /// Backends which can't choose the tier return `ApiError::UnsupportedOperation`.
+    async fn add_tracker_tier(
+        &self,
+        _hash: &SingleTarget,
+        _tracker: &str,
+        _tier: u32,
+    ) -> Result<(), ApiError> {
+        Err(unsupported(Operation::AddTrackerTier))
+    }
+    /// Replaces the URL of a tracker of a torrent, keeping its tier.
+    ///
+    /// Backends which can't edit trackers return `ApiError::UnsupportedOperation`.
+    async fn edit_tracker(
+        &self,
+        _hash: &SingleTarget,
+        _from: &str,
+        _to: &str,
+    ) -> Result<(), ApiError> {
+        Err(unsupported(Operation::EditTracker))
+    }
+    /// Returns the trackers of a torrent with their tier, status and statistics, along with the
+    /// DHT, PeX and LSD peer sources.
+    ///
+    /// Backends without tracker details return `ApiError::UnsupportedOperation`.
+    async fn get_tracker_details(&self, _hash: &SingleTarget) -> Result<TrackerDetails, ApiError> {
+        Err(unsupported(Operation::GetTrackerDetails))
+    }
 
     async fn get_files(&self, hash: &SingleTarget) -> Result<Vec<TorrentContent>, ApiError>;
+    /// Returns the category and limits of a torrent.
+    ///
+    /// Backends without per-torrent settings return `ApiError::UnsupportedOperation`.
+    async fn get_settings(&self, _hash: &SingleTarget) -> Result<TorrentSettings, ApiError> {
+        Err(unsupported(Operation::GetSettings))
+    }
+
+    // Torrent metainfo export
+    /// Returns the raw .torrent file for the requested torrent.
+    ///
+    /// Backends which can't export torrents return `ApiError::UnsupportedOperation`.
+    async fn export(&self, _hash: &SingleTarget) -> Result<Vec<u8>, ApiError> {
+        Err(unsupported(Operation::Export))
+    }
+
+    /// Returns the parsed .torrent file for the requested torrent
+    async fn export_parsed(&self, hash: &SingleTarget) -> Result<TorrentFile, ApiError> {
+        let bytes = self.export(hash).await?;
+        TorrentFile::from_slice(&bytes).context(InvalidTorrentError)
+    }
+
+    /// Writes the .torrent files for the requested torrents into a directory.
+    ///
+    /// Files are named after the torrent infohash (`INFOHASH.torrent`, with the v2 infohash for
+    /// hybrid torrents). Returns the paths of the written files.
+    async fn export_to_dir(
+        &self,
+        target: &MultiTarget,
+        dir: &Path,
+    ) -> Result<Vec<PathBuf>, ApiError> {
+        let torrents = match target {
+            MultiTarget::All => self.list().await?.to_vec(),
+            MultiTarget::Hash(single_target) => match self.get(single_target).await? {
+                Some(torrent) => vec![torrent],
+                None => {
+                    return Err(ApiError::MissingTorrent {
+                        hash: single_target.to_string(),
+                    });
+                }
+            },
+        };
+
+        let mut paths = Vec::with_capacity(torrents.len());
+        for torrent in torrents {
+            let bytes = self.export(&SingleTarget::from(&torrent.hash)).await?;
+            let path = dir.join(format!("{}.torrent", torrent.hash.as_str()));
+            tokio::fs::write(&path, bytes)
+                .await
+                .context(FailedWriteTorrentError { path: path.clone() })?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+/// Error returned by the default implementation of the optional [Api] operations
+fn unsupported(operation: Operation) -> ApiError {
+    ApiError::UnsupportedOperation {
+        endpoint: format!("{operation:?}"),
+        message: "not implemented by this backend".to_string(),
+    }
 }
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+/// Per-torrent settings, matching the options of the [AddBuilder](crate::api::AddBuilder).
+///
+/// `None` means the setting is not set on the torrent (no category, no limit, or global limit).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TorrentSettings {
+    pub category: Option<String>,
+    /// Upload limit in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Download limit in bytes per second
+    pub download_limit: Option<u64>,
+    pub ratio_limit: Option<f64>,
+    pub seeding_time_limit: Option<Duration>,
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// Status of a torrent's tracker, as last reported by the backend
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackerStatus {
+    /// The tracker is disabled (for example private torrents disable DHT/PeX/LSD)
+    Disabled,
+    /// The tracker was not contacted yet
+    NotContacted,
+    Working,
+    /// The tracker is being announced to
+    Updating,
+    NotWorking,
+    /// A status unknown to this library, reported by a newer backend
+    Unknown,
+}
+
+/// A tracker of a torrent, or a DHT/PeX/LSD peer source, with its announce statistics.
+///
+/// Statistics are `None` when the backend does not know them (not announced yet).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackerEntry {
+    /// Announce URL, or a description for DHT/PeX/LSD
+    pub url: String,
+    /// Tier of the tracker, starting at 0; `None` for DHT/PeX/LSD, or when the backend
+    /// does not know it
+    pub tier: Option<u32>,
+    pub status: TrackerStatus,
+    pub seeds: Option<u64>,
+    pub peers: Option<u64>,
+    pub leeches: Option<u64>,
+    /// Number of completed downloads reported by the tracker
+    pub downloaded: Option<u64>,
+    /// Last message from the tracker, empty if there was none
+    pub message: String,
+}
+
+/// The trackers of a torrent, as returned by [Api::get_tracker_details](crate::Api::get_tracker_details)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackerDetails {
+    /// Actual trackers, ordered by tier, with those without a tier last
+    pub trackers: Vec<TrackerEntry>,
+    /// Distributed Hash Table, when reported by the backend
+    pub dht: Option<TrackerEntry>,
+    /// Peer Exchange, when reported by the backend
+    pub pex: Option<TrackerEntry>,
+    /// Local Service Discovery, when reported by the backend
+    pub lsd: Option<TrackerEntry>,
+}
@@ -13,6 +13,31 @@ pub enum ApiError {
     InvalidLogin { host: String, user: String },
     #[snafu(display("API backend rejected the torrent as invalid"))]
     RejectedTorrent,
+    #[snafu(display("API backend already has the torrent {hash}"))]
+    DuplicateTorrent { hash: String },
+    #[snafu(display("Timed out waiting for torrent {hash} to be added"))]
+    AddTimeout { hash: String },
+    #[snafu(display("Invalid torrent file:\n{source}"))]
+    InvalidTorrent {
+        source: hightorrent::TorrentFileError,
+    },
+    #[snafu(display("Invalid magnet link:\n{source}"))]
+    InvalidMagnet {
+        source: hightorrent::MagnetLinkError,
+    },
+    #[snafu(display("API backend does not support the add option {option}"))]
+    UnsupportedAddOption { option: crate::api::AddOption },
+    #[snafu(display(
+        "Cannot wait for the torrent from {url}, whose infohash is only known to the Bittorrent client"
+    ))]
+    UnsupportedWait { url: String },
+    #[snafu(display("Invalid value {value} for the add option {option}"))]
+    InvalidAddOption {
+        option: crate::api::AddOption,
+        value: String,
+    },
+    #[snafu(display("Torrent queueing is disabled on API backend"))]
+    QueueingDisabled,
     #[snafu(display("Torrent hash not found {hash}"))]
     MissingTorrent { hash: String },
     #[snafu(display("Failed to read torrent file from path {}:\n{source}", path.display()))]
@@ -20,10 +45,94 @@ pub enum ApiError {
         source: std::io::Error,
         path: std::path::PathBuf,
     },
+    #[snafu(display("Failed to write torrent file to path {}:\n{source}", path.display()))]
+    FailedWriteTorrent {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[snafu(display("Torrent {hash} has no metadata yet"))]
+    MissingMetadata { hash: String },
+    #[snafu(display("Failed to serialize data:\n{source}"))]
+    Serialization { source: serde_json::Error },
+    #[snafu(display("Failed to read/write backup at path {}:\n{source}", path.display()))]
+    BackupIo {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[snafu(display("Failed to read/write migration progress log at path {}:\n{source}", path.display()))]
+    MigrationLog {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[snafu(display("Unsupported backup format version {version}"))]
+    UnsupportedBackupVersion { version: u32 },
     #[snafu(display("Invalid infohash: {source}"))]
     InfoHash { source: hightorrent::InfoHashError },
+    #[snafu(display("Failed to read configuration at path {}:\n{source}", path.display()))]
+    ConfigIo {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[snafu(display("Invalid configuration:\n{source}"))]
+    InvalidConfig {
+        source: Box<dyn std::error::Error + 'static + Send + Sync>,
+    },
+    #[snafu(display("Profile not found in configuration: {name}"))]
+    MissingProfile { name: String },
+    #[snafu(display("Unsupported API backend {backend}"))]
+    UnsupportedBackend { backend: String },
+    #[snafu(display("Failed to read/write session at path {}:\n{source}", path.display()))]
+    SessionStore {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[snafu(display("Invalid version number {version}"))]
+    InvalidVersion { version: String },
     #[snafu(display("Failed to initialize the torrent API client:\n{source}"))]
     ClientInit {
         source: Box<dyn std::error::Error + 'static + Send + Sync>,
     },
+    #[snafu(display("Invalid API backend URL {host}, expected http(s)://host[:port][/path]"))]
+    InvalidHost { host: String },
+    #[snafu(display("Request to API backend timed out:\n{source}"))]
+    Timeout {
+        source: Box<dyn std::error::Error + 'static + Send + Sync>,
+    },
+    #[snafu(display("API backend refused the request: {message}"))]
+    Forbidden { message: String },
+    #[snafu(display("API backend banned this IP address: {message}"))]
+    Banned { message: String },
+    #[snafu(display("API backend does not support {endpoint}: {message}"))]
+    UnsupportedOperation { endpoint: String, message: String },
+    #[snafu(display("API backend reported a conflict: {message}"))]
+    Conflict { message: String },
+    #[snafu(display("API backend rejected an invalid argument: {message}"))]
+    InvalidArgument { message: String },
+    #[snafu(display("API backend failed with HTTP status {status}: {body}"))]
+    Server { status: u16, body: String },
+}
+
+impl ApiError {
+    /// Whether the same request may succeed later: network errors, timeouts, and server
+    /// errors (HTTP 5xx).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::Http { .. } | ApiError::Timeout { .. } => true,
+            ApiError::Server { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// The raw message returned by the API backend, if any
+    pub fn backend_message(&self) -> Option<&str> {
+        match self {
+            ApiError::Forbidden { message }
+            | ApiError::Banned { message }
+            | ApiError::UnsupportedOperation { message, .. }
+            | ApiError::Conflict { message }
+            | ApiError::InvalidArgument { message } => Some(message),
+            ApiError::Server { body, .. } => Some(body),
+            _ => None,
+        }
+    }
 }
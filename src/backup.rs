@@ -0,0 +1,548 @@
+//! Backup and restore torrents across any [Api](crate::Api) backend.
+//!
+//! A backup is a directory containing a `manifest.json` file, describing every torrent
+//! (save path, category, tags, trackers, limits and state), and a `torrents` subdirectory
+//! with the exported .torrent files, named after their infohash.
+//!
+//! ```no_run
+//! use hightorrent::MultiTarget;
+//! use hightorrent_api::{Api, QBittorrentClient};
+//! use hightorrent_api::backup::{backup, restore, RestoreOptions};
+//! use std::path::Path;
+//!
+//! # async fn run() -> Result<(), hightorrent_api::ApiError> {
+//! let old = QBittorrentClient::login("http://old:8080", "admin", "adminadmin").await?;
+//! let new = QBittorrentClient::login("http://new:8080", "admin", "adminadmin").await?;
+//!
+//! let report = backup(&old, &MultiTarget::All, Path::new("/tmp/backup")).await?;
+//! for failure in report.failures {
+//!     println!("{}: {}", failure.hash, failure.error);
+//! }
+//! let report = restore(&new, Path::new("/tmp/backup"), &RestoreOptions::default()).await?;
+//! for entry in report.entries {
+//!     println!("{}: {:?}", entry.hash, entry.result);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use hightorrent::{InfoHash, MultiTarget, SingleTarget, Torrent};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::api::{
+    AddBuilder, AddRef, AddSource, AddWait, SeedingLimit, TorrentSettings, wait_added,
+};
+use crate::api_error::*;
+use crate::{Api, ApiError};
+
+/// Version of the backup format produced by this release
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Name of the manifest file in a backup directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Name of the .torrent files subdirectory in a backup directory
+pub const TORRENTS_DIR: &str = "torrents";
+
+/// How long to wait for a restored torrent to appear in the backend, by default
+pub const DEFAULT_ADD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Describes all the torrents in a backup
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub torrents: Vec<BackupEntry>,
+}
+
+/// Describes a single torrent in a backup
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub hash: InfoHash,
+    pub name: String,
+    pub save_path: String,
+    pub tags: Vec<String>,
+    pub trackers: Vec<BackupTracker>,
+    pub settings: TorrentSettings,
+    /// State of the torrent in the backend at the time of the backup
+    pub state: String,
+    pub paused: bool,
+    /// Path of the .torrent file, relative to the backup directory.
+    ///
+    /// None when the torrent had no metadata yet, in which case it is restored from a magnet.
+    pub torrent_file: Option<PathBuf>,
+}
+
+/// A tracker of a torrent in a backup
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BackupTracker {
+    pub url: String,
+    /// Tier of the tracker, starting at 0; `None` when the backend does not report tiers
+    pub tier: Option<u32>,
+}
+
+impl BackupEntry {
+    /// Builds a magnet link from the infohash, used when no .torrent file is available
+    pub fn magnet(&self) -> String {
+        match &self.hash {
+            InfoHash::V1(v1) => format!("magnet:?xt=urn:btih:{v1}"),
+            InfoHash::V2(v2) => format!("magnet:?xt=urn:btmh:1220{v2}"),
+            InfoHash::Hybrid((v1, v2)) => {
+                format!("magnet:?xt=urn:btih:{v1}&xt=urn:btmh:1220{v2}")
+            }
+        }
+    }
+}
+
+/// Options to restore a backup
+#[derive(Clone, Debug)]
+pub struct RestoreOptions {
+    /// Skip checking existing files, for torrents restored from a .torrent file
+    pub skip_checking: bool,
+    /// How long to wait for every restored torrent to appear in the backend, before reading
+    /// it back to add its trackers
+    pub add_timeout: Duration,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> RestoreOptions {
+        RestoreOptions {
+            skip_checking: false,
+            add_timeout: DEFAULT_ADD_TIMEOUT,
+        }
+    }
+}
+
+/// The outcome of a backup
+#[derive(Debug)]
+pub struct BackupReport {
+    /// The written manifest, describing the torrents which were backed up
+    pub manifest: BackupManifest,
+    /// Torrents which could not be backed up, and are missing from the manifest
+    pub failures: Vec<BackupFailure>,
+}
+
+#[derive(Debug)]
+pub struct BackupFailure {
+    pub hash: InfoHash,
+    pub name: String,
+    pub error: ApiError,
+}
+
+/// The outcome of a restore, for every torrent in the backup
+#[derive(Debug)]
+pub struct RestoreReport {
+    pub entries: Vec<RestoreEntryReport>,
+}
+
+#[derive(Debug)]
+pub struct RestoreEntryReport {
+    pub hash: InfoHash,
+    pub result: RestoreResult,
+    /// Differences between the backup and the restored torrent
+    pub differences: Vec<RestoreDifference>,
+}
+
+#[derive(Debug)]
+pub enum RestoreResult {
+    Restored,
+    /// The backend already had this torrent, which was left untouched
+    Duplicate,
+    Failed(ApiError),
+}
+
+/// A field of the restored torrent which does not match the backup
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestoreDifference {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Exports the requested torrents into a backup directory, returning the written manifest.
+///
+/// The directory is created if it does not exist. Torrents which fail to back up do not stop
+/// the backup; their error is in the report.
+pub async fn backup<A: Api + ?Sized>(
+    api: &A,
+    target: &MultiTarget,
+    dir: &Path,
+) -> Result<BackupReport, ApiError> {
+    let torrents_dir = dir.join(TORRENTS_DIR);
+    tokio::fs::create_dir_all(&torrents_dir)
+        .await
+        .context(BackupIoError {
+            path: torrents_dir.clone(),
+        })?;
+
+    let torrents = match target {
+        MultiTarget::All => api.list().await?.to_vec(),
+        MultiTarget::Hash(single_target) => match api.get(single_target).await? {
+            Some(torrent) => vec![torrent],
+            None => {
+                return Err(ApiError::MissingTorrent {
+                    hash: single_target.to_string(),
+                });
+            }
+        },
+    };
+
+    let mut entries = Vec::with_capacity(torrents.len());
+    let mut failures = Vec::new();
+    for torrent in torrents {
+        let (hash, name) = (torrent.hash.clone(), torrent.name.clone());
+        match backup_entry(api, torrent, dir).await {
+            Ok(entry) => entries.push(entry),
+            Err(error) => failures.push(BackupFailure { hash, name, error }),
+        }
+    }
+
+    let manifest = BackupManifest {
+        version: BACKUP_VERSION,
+        torrents: entries,
+    };
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let json = serde_json::to_vec_pretty(&manifest).context(SerializationError)?;
+    tokio::fs::write(&manifest_path, json)
+        .await
+        .context(BackupIoError {
+            path: manifest_path,
+        })?;
+
+    Ok(BackupReport { manifest, failures })
+}
+
+async fn backup_entry<A: Api + ?Sized>(
+    api: &A,
+    torrent: Torrent,
+    dir: &Path,
+) -> Result<BackupEntry, ApiError> {
+    let (mut entry, bytes) = describe(api, torrent).await?;
+
+    if let Some(bytes) = bytes {
+        let relative = Path::new(TORRENTS_DIR).join(format!("{}.torrent", entry.hash));
+        let path = dir.join(&relative);
+        tokio::fs::write(&path, bytes)
+            .await
+            .context(FailedWriteTorrentError { path })?;
+        entry.torrent_file = Some(relative);
+    }
+
+    Ok(entry)
+}
+
+/// Describes a torrent from the backend, along with its exported .torrent file.
+///
+/// The .torrent file is None when the torrent has no metadata yet. The returned entry
+/// never has a `torrent_file`, which is up to the caller.
+pub(crate) async fn describe<A: Api + ?Sized>(
+    api: &A,
+    torrent: Torrent,
+) -> Result<(BackupEntry, Option<Vec<u8>>), ApiError> {
+    let target = SingleTarget::from(&torrent.hash);
+
+    // Torrents which can't be exported are restored from their magnet
+    let bytes = match api.export(&target).await {
+        Ok(bytes) => Some(bytes),
+        Err(ApiError::MissingMetadata { .. } | ApiError::UnsupportedOperation { .. }) => None,
+        Err(e) => return Err(e),
+    };
+
+    let settings = match api.get_settings(&target).await {
+        Ok(settings) => settings,
+        Err(ApiError::UnsupportedOperation { .. }) => TorrentSettings::default(),
+        Err(e) => return Err(e),
+    };
+
+    let entry = BackupEntry {
+        trackers: read_trackers(api, &target).await?,
+        settings,
+        paused: is_paused(&torrent.state),
+        tags: clean_tags(torrent.tags),
+        hash: torrent.hash,
+        name: torrent.name,
+        save_path: torrent.path,
+        state: torrent.state,
+        torrent_file: None,
+    };
+
+    Ok((entry, bytes))
+}
+
+/// Reads the manifest from a backup directory
+pub async fn read_manifest(dir: &Path) -> Result<BackupManifest, ApiError> {
+    let path = dir.join(MANIFEST_FILE);
+    let json = tokio::fs::read(&path)
+        .await
+        .context(BackupIoError { path })?;
+    let manifest: BackupManifest = serde_json::from_slice(&json).context(DeserializationError)?;
+
+    if manifest.version > BACKUP_VERSION {
+        return Err(ApiError::UnsupportedBackupVersion {
+            version: manifest.version,
+        });
+    }
+
+    Ok(manifest)
+}
+
+/// Restores all torrents from a backup directory, reporting the outcome for each of them.
+///
+/// Torrents which fail to restore do not stop the restore; their error is in the report.
+pub async fn restore<A: Api + ?Sized>(
+    api: &A,
+    dir: &Path,
+    options: &RestoreOptions,
+) -> Result<RestoreReport, ApiError> {
+    let manifest = read_manifest(dir).await?;
+
+    let mut entries = Vec::with_capacity(manifest.torrents.len());
+    for entry in manifest.torrents {
+        let (result, differences) = match restore_entry(api, &entry, dir, options).await {
+            Ok(()) => match compare(api, &entry).await {
+                Ok(differences) => (RestoreResult::Restored, differences),
+                Err(e) => (RestoreResult::Failed(e), Vec::new()),
+            },
+            Err(ApiError::DuplicateTorrent { .. }) => (RestoreResult::Duplicate, Vec::new()),
+            Err(e) => (RestoreResult::Failed(e), Vec::new()),
+        };
+
+        entries.push(RestoreEntryReport {
+            hash: entry.hash,
+            result,
+            differences,
+        });
+    }
+
+    Ok(RestoreReport { entries })
+}
+
+async fn restore_entry<A: Api + ?Sized>(
+    api: &A,
+    entry: &BackupEntry,
+    dir: &Path,
+    options: &RestoreOptions,
+) -> Result<(), ApiError> {
+    let source = match &entry.torrent_file {
+        Some(torrent_file) => AddSource::torrent_file(&dir.join(torrent_file)),
+        None => AddSource::magnet(&entry.magnet()),
+    };
+    add_entry(api, entry, source, options.skip_checking).await?;
+    complete_entry(api, entry, options.add_timeout).await
+}
+
+/// Adds a torrent described by a backup entry, with the same options.
+///
+/// Its trackers are added by [complete_entry]. Skip checking is only enabled for .torrent
+/// files, because magnets have no files to check.
+pub(crate) async fn add_entry<A: Api + ?Sized>(
+    api: &A,
+    entry: &BackupEntry,
+    source: AddSource,
+    skip_checking: bool,
+) -> Result<(), ApiError> {
+    let is_magnet = matches!(source, AddSource::MagnetStr(_));
+    let api_add = AddRef(api);
+    let mut add = AddBuilder::new(&api_add)
+        .save_path(&entry.save_path)
+        .paused(entry.paused);
+    if !entry.tags.is_empty() {
+        add = add.tags(entry.tags.clone());
+    }
+    if let Some(category) = &entry.settings.category {
+        add = add.category(category);
+    }
+    if let Some(upload_limit) = entry.settings.upload_limit {
+        add = add.upload_limit(upload_limit);
+    }
+    if let Some(download_limit) = entry.settings.download_limit {
+        add = add.download_limit(download_limit);
+    }
+    if let Some(ratio_limit) = entry.settings.ratio_limit {
+        add = add.ratio_limit(SeedingLimit::Limit(ratio_limit));
+    }
+    if let Some(seeding_time_limit) = entry.settings.seeding_time_limit {
+        add = add.seeding_time_limit(SeedingLimit::Limit(seeding_time_limit));
+    }
+    if skip_checking && !is_magnet {
+        add = add.skip_checking(true);
+    }
+
+    add.source(source).send().await?;
+    Ok(())
+}
+
+/// Adds the trackers of a backup entry to its torrent, once the torrent appears in the
+/// backend, failing after `timeout`.
+pub(crate) async fn complete_entry<A: Api + ?Sized>(
+    api: &A,
+    entry: &BackupEntry,
+    timeout: Duration,
+) -> Result<(), ApiError> {
+    // The backend may add torrents asynchronously, so wait before reading its trackers
+    let wait = AddWait {
+        timeout,
+        metadata: false,
+    };
+    wait_added(&AddRef(api), &entry.hash, wait).await?;
+
+    // Trackers from the magnet/torrent file are already there
+    let target = SingleTarget::from(&entry.hash);
+    let existing = read_trackers(api, &target).await?;
+    for tracker in &entry.trackers {
+        if existing.iter().any(|existing| existing.url == tracker.url) {
+            continue;
+        }
+        match tracker.tier {
+            Some(tier) => match api.add_tracker_tier(&target, &tracker.url, tier).await {
+                Err(ApiError::UnsupportedOperation { .. }) => {
+                    api.add_tracker(&target, &tracker.url).await?
+                }
+                result => result?,
+            },
+            None => api.add_tracker(&target, &tracker.url).await?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the trackers of a torrent with their tier, or without it when the backend has no
+/// tracker details
+async fn read_trackers<A: Api + ?Sized>(
+    api: &A,
+    target: &SingleTarget,
+) -> Result<Vec<BackupTracker>, ApiError> {
+    match api.get_tracker_details(target).await {
+        Ok(details) => Ok(details
+            .trackers
+            .into_iter()
+            .map(|tracker| BackupTracker {
+                url: tracker.url,
+                tier: tracker.tier,
+            })
+            .collect()),
+        Err(ApiError::UnsupportedOperation { .. }) => Ok(api
+            .get_trackers(target)
+            .await?
+            .iter()
+            .map(|tracker| BackupTracker {
+                url: tracker.url().to_string(),
+                tier: None,
+            })
+            .collect()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Compares the restored torrent with its backup entry
+pub(crate) async fn compare<A: Api + ?Sized>(
+    api: &A,
+    entry: &BackupEntry,
+) -> Result<Vec<RestoreDifference>, ApiError> {
+    let target = SingleTarget::from(&entry.hash);
+    let Some(torrent) = api.get(&target).await? else {
+        return Err(ApiError::MissingTorrent {
+            hash: entry.hash.to_string(),
+        });
+    };
+
+    let mut differences = Vec::new();
+    let mut check = |field: &'static str, expected: String, actual: String| {
+        if expected != actual {
+            differences.push(RestoreDifference {
+                field,
+                expected,
+                actual,
+            });
+        }
+    };
+
+    check("save_path", entry.save_path.clone(), torrent.path);
+
+    let mut expected_tags = entry.tags.clone();
+    expected_tags.sort();
+    let mut actual_tags = clean_tags(torrent.tags);
+    actual_tags.sort();
+    check("tags", expected_tags.join(","), actual_tags.join(","));
+
+    // Settings are not compared when the backend can't read them
+    match api.get_settings(&target).await {
+        Ok(settings) => {
+            let expected = &entry.settings;
+            check(
+                "category",
+                setting(&expected.category),
+                setting(&settings.category),
+            );
+            check(
+                "upload_limit",
+                setting(&expected.upload_limit),
+                setting(&settings.upload_limit),
+            );
+            check(
+                "download_limit",
+                setting(&expected.download_limit),
+                setting(&settings.download_limit),
+            );
+            check(
+                "ratio_limit",
+                setting(&expected.ratio_limit),
+                setting(&settings.ratio_limit),
+            );
+            check(
+                "seeding_time_limit",
+                setting(&expected.seeding_time_limit.map(|limit| limit.as_secs())),
+                setting(&settings.seeding_time_limit.map(|limit| limit.as_secs())),
+            );
+        }
+        Err(ApiError::UnsupportedOperation { .. }) => {}
+        Err(e) => return Err(e),
+    }
+
+    // Tiers are only compared when both the backup and the backend know them
+    let actual_trackers = read_trackers(api, &target).await?;
+    let tiers = entry
+        .trackers
+        .iter()
+        .chain(&actual_trackers)
+        .all(|tracker| tracker.tier.is_some());
+    check(
+        "trackers",
+        trackers_string(&entry.trackers, tiers),
+        trackers_string(&actual_trackers, tiers),
+    );
+
+    Ok(differences)
+}
+
+/// Formats an optional setting, which is empty when not set
+fn setting<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+/// Formats trackers in a stable order, prefixed with their tier when `tiers` is true
+fn trackers_string(trackers: &[BackupTracker], tiers: bool) -> String {
+    let mut trackers: Vec<String> = trackers
+        .iter()
+        .map(|tracker| match (tiers, tracker.tier) {
+            (true, Some(tier)) => format!("{tier}:{}", tracker.url),
+            _ => tracker.url.to_string(),
+        })
+        .collect();
+    trackers.sort();
+    trackers.join(",")
+}
+
+/// Whether a backend state string means the torrent is paused/stopped
+fn is_paused(state: &str) -> bool {
+    state.starts_with("stopped") || state.starts_with("paused")
+}
+
+/// Removes empty tags, which some backends return when a torrent has no tags
+fn clean_tags(tags: Vec<String>) -> Vec<String> {
+    tags.into_iter().filter(|tag| !tag.is_empty()).collect()
+}
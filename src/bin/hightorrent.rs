@@ -0,0 +1,613 @@
+//! hightorrent command-line interface, for everyday operations on torrent clients.
+//!
+//! Connection settings are read from named profiles in a TOML configuration file
+//! (`$XDG_CONFIG_HOME/hightorrent/config.toml` by default), and may be overridden
+//! with command-line flags or environment variables:
+//!
+//! ```toml
+//! [profiles.default]
+//! url = "http://localhost:8080"
+//! user = "admin"
+//! password = "adminadmin"
+//! ```
+//!
+//! See [hightorrent_api::config] for all the profile settings.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use hightorrent::{MultiTarget, SingleTarget, Torrent};
+use hightorrent_api::Api;
+use hightorrent_api::api::{ContentLayout, SeedingLimit, StopCondition};
+use hightorrent_api::config::{Backend, Config, Profile};
+use hightorrent_api::trackers::{TrackerReplacement, TrackerReplacementResult, replace_trackers};
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(
+    name = "hightorrent",
+    version,
+    about = "Manage torrents in a torrent client"
+)]
+struct Cli {
+    /// Configuration file containing connection profiles
+    #[arg(long, env = "HIGHTORRENT_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Connection profile to use from the configuration file
+    #[arg(short, long, env = "HIGHTORRENT_PROFILE", default_value = "default")]
+    profile: String,
+
+    /// Torrent client URL, overriding the profile
+    #[arg(long, env = "HIGHTORRENT_HOST")]
+    host: Option<String>,
+
+    /// Torrent client user, overriding the profile
+    #[arg(long, env = "HIGHTORRENT_USER")]
+    user: Option<String>,
+
+    /// Torrent client password, overriding the profile
+    #[arg(long, env = "HIGHTORRENT_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List torrents
+    List {
+        /// Only list torrents in this state
+        #[arg(long)]
+        state: Option<String>,
+        /// Only list torrents with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only list torrents whose name contains this string (case-insensitive)
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, value_enum, default_value_t = Output::Table)]
+        output: Output,
+    },
+    /// Show detailed information about a torrent
+    Info {
+        hash: SingleTarget,
+        #[arg(long, value_enum, default_value_t = Output::Table)]
+        output: Output,
+    },
+    /// Add a torrent from a magnet link, a .torrent file or an HTTP(S) URL
+    Add(AddArgs),
+    /// Remove a torrent
+    Remove {
+        hash: SingleTarget,
+        /// Also delete the downloaded files
+        #[arg(long)]
+        delete_files: bool,
+    },
+    /// List the trackers of a torrent, or add/remove trackers
+    Trackers {
+        hash: SingleTarget,
+        /// Add a tracker URL
+        #[arg(long, conflicts_with = "remove")]
+        add: Option<String>,
+        /// Remove a tracker URL
+        #[arg(long, conflicts_with = "edit")]
+        remove: Option<String>,
+        /// Replace a tracker URL, keeping its tier
+        #[arg(long, num_args = 2, value_names = ["FROM", "TO"], conflicts_with = "add")]
+        edit: Option<Vec<String>>,
+        #[arg(long, value_enum, default_value_t = Output::Table)]
+        output: Output,
+    },
+    /// Replace a pattern in the tracker URLs of all torrents (or a single torrent), keeping
+    /// their tier
+    ReplaceTrackers {
+        pattern: String,
+        replacement: String,
+        /// Only replace the trackers of this torrent
+        #[arg(long)]
+        hash: Option<SingleTarget>,
+        /// Print the trackers which would be replaced, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the files of a torrent
+    Files {
+        hash: SingleTarget,
+        #[arg(long, value_enum, default_value_t = Output::Table)]
+        output: Output,
+    },
+    /// Move the files of a torrent to another location
+    SetLocation {
+        hash: SingleTarget,
+        location: String,
+    },
+}
+
+#[derive(Args)]
+struct AddArgs {
+    /// Magnet link, path to a .torrent/.magnet file, or HTTP(S) URL to a .torrent file
+    source: String,
+    #[arg(long)]
+    save_path: Option<String>,
+    /// Add the torrent paused
+    #[arg(long, overrides_with = "no_paused")]
+    paused: bool,
+    /// Start the torrent right away
+    #[arg(long, overrides_with = "paused")]
+    no_paused: bool,
+    /// Comma-separated list of tags
+    #[arg(long, value_delimiter = ',')]
+    tags: Option<Vec<String>>,
+    #[arg(long)]
+    category: Option<String>,
+    #[arg(long)]
+    rename: Option<String>,
+    /// Skip hash checking of existing files
+    #[arg(long, overrides_with = "no_skip_checking")]
+    skip_checking: bool,
+    /// Check existing files
+    #[arg(long, overrides_with = "skip_checking")]
+    no_skip_checking: bool,
+    #[arg(long, value_enum)]
+    content_layout: Option<CliContentLayout>,
+    #[arg(long, value_enum)]
+    stop_condition: Option<CliStopCondition>,
+    /// Enable automatic torrent management
+    #[arg(long, overrides_with = "no_auto_management")]
+    auto_management: bool,
+    /// Disable automatic torrent management
+    #[arg(long, overrides_with = "auto_management")]
+    no_auto_management: bool,
+    #[arg(long)]
+    download_path: Option<String>,
+    /// Upload limit in bytes per second
+    #[arg(long)]
+    upload_limit: Option<u64>,
+    /// Download limit in bytes per second
+    #[arg(long)]
+    download_limit: Option<u64>,
+    /// Ratio limit, or `global` or `unlimited`
+    #[arg(long, value_parser = parse_ratio_limit)]
+    ratio_limit: Option<SeedingLimit<f64>>,
+    /// Seeding time limit in minutes, or `global` or `unlimited`
+    #[arg(long, value_parser = parse_seeding_time_limit)]
+    seeding_time_limit: Option<SeedingLimit<Duration>>,
+    /// Download pieces in order
+    #[arg(long, overrides_with = "no_sequential_download")]
+    sequential_download: bool,
+    /// Download pieces in any order
+    #[arg(long, overrides_with = "sequential_download")]
+    no_sequential_download: bool,
+    /// Download the first and last pieces first
+    #[arg(long, overrides_with = "no_first_last_piece_priority")]
+    first_last_piece_priority: bool,
+    /// Don't prioritize the first and last pieces
+    #[arg(long, overrides_with = "first_last_piece_priority")]
+    no_first_last_piece_priority: bool,
+    /// Add the torrent at the top of the queue
+    #[arg(long, overrides_with = "no_add_to_top_of_queue")]
+    add_to_top_of_queue: bool,
+    /// Add the torrent at the bottom of the queue
+    #[arg(long, overrides_with = "add_to_top_of_queue")]
+    no_add_to_top_of_queue: bool,
+    /// Wait up to this many seconds for the torrent to appear in the torrent client
+    #[arg(long)]
+    wait: Option<u64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CliContentLayout {
+    Original,
+    Subfolder,
+    NoSubfolder,
+}
+
+impl From<CliContentLayout> for ContentLayout {
+    fn from(l: CliContentLayout) -> ContentLayout {
+        match l {
+            CliContentLayout::Original => ContentLayout::Original,
+            CliContentLayout::Subfolder => ContentLayout::Subfolder,
+            CliContentLayout::NoSubfolder => ContentLayout::NoSubfolder,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CliStopCondition {
+    None,
+    MetadataReceived,
+    FilesChecked,
+}
+
+impl From<CliStopCondition> for StopCondition {
+    fn from(c: CliStopCondition) -> StopCondition {
+        match c {
+            CliStopCondition::None => StopCondition::None,
+            CliStopCondition::MetadataReceived => StopCondition::MetadataReceived,
+            CliStopCondition::FilesChecked => StopCondition::FilesChecked,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> CliResult<()> {
+    let profile = profile(&cli)?;
+
+    let client = match &cli.command {
+        // Moving files is specific to qBittorrent, outside of the Api trait
+        Command::SetLocation { hash, location } => {
+            let client = match profile.backend {
+                Backend::QBittorrent => profile.connect_qbittorrent().await?,
+            };
+            client.set_location(hash, location).await?;
+            return Ok(());
+        }
+        _ => profile.connect().await?,
+    };
+
+    match cli.command {
+        Command::List {
+            state,
+            tag,
+            name,
+            output,
+        } => {
+            let name = name.map(|name| name.to_lowercase());
+            let torrents: Vec<Torrent> = client
+                .list()
+                .await?
+                .into_iter()
+                .filter(|torrent| state.as_ref().is_none_or(|state| &torrent.state == state))
+                .filter(|torrent| tag.as_ref().is_none_or(|tag| torrent.tags.contains(tag)))
+                .filter(|torrent| {
+                    name.as_ref()
+                        .is_none_or(|name| torrent.name.to_lowercase().contains(name))
+                })
+                .collect();
+            print_torrents(&torrents, output)?;
+        }
+        Command::Info { hash, output } => {
+            let Some(torrent) = client.get(&hash).await? else {
+                return Err(format!("Torrent not found: {hash}").into());
+            };
+            match output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&torrent)?),
+                Output::Table => {
+                    print_rows(&[
+                        vec!["Name".to_string(), torrent.name.to_string()],
+                        vec!["Hash".to_string(), torrent.hash.to_string()],
+                        vec!["ID".to_string(), torrent.id.to_string()],
+                        vec!["State".to_string(), torrent.state.to_string()],
+                        vec!["Progress".to_string(), format!("{}%", torrent.progress)],
+                        vec!["Size".to_string(), torrent.size.to_string()],
+                        vec!["Path".to_string(), torrent.path.to_string()],
+                        vec!["Tags".to_string(), torrent.tags.join(",")],
+                        vec!["Added".to_string(), torrent.date_start.to_string()],
+                        vec!["Completed".to_string(), torrent.date_end.to_string()],
+                    ]);
+                }
+            }
+        }
+        Command::Add(args) => {
+            let hash = add(&*client, args).await?;
+            if let Some(hash) = hash {
+                println!("{hash}");
+            }
+        }
+        Command::Remove { hash, delete_files } => {
+            client.remove(&hash, delete_files).await?;
+        }
+        Command::Trackers {
+            hash,
+            add,
+            remove,
+            edit,
+            output,
+        } => {
+            if let Some(tracker) = add {
+                client.add_tracker(&hash, &tracker).await?;
+            } else if let Some(tracker) = remove {
+                client.remove_tracker(&hash, &tracker).await?;
+            } else if let Some([from, to]) = edit.as_deref() {
+                client.edit_tracker(&hash, from, to).await?;
+            } else {
+                let details = client.get_tracker_details(&hash).await?;
+                match output {
+                    Output::Json => println!("{}", serde_json::to_string_pretty(&details)?),
+                    Output::Table => {
+                        let count = |count: Option<u64>| count.map(|c| c.to_string());
+                        let mut rows = vec![
+                            [
+                                "TIER",
+                                "STATUS",
+                                "SEEDS",
+                                "PEERS",
+                                "LEECHES",
+                                "DOWNLOADED",
+                                "URL",
+                                "MESSAGE",
+                            ]
+                            .map(String::from)
+                            .to_vec(),
+                        ];
+                        rows.extend(details.trackers.iter().map(|tracker| {
+                            vec![
+                                tracker.tier.map(|t| t.to_string()).unwrap_or_default(),
+                                format!("{:?}", tracker.status),
+                                count(tracker.seeds).unwrap_or("-".to_string()),
+                                count(tracker.peers).unwrap_or("-".to_string()),
+                                count(tracker.leeches).unwrap_or("-".to_string()),
+                                count(tracker.downloaded).unwrap_or("-".to_string()),
+                                tracker.url.to_string(),
+                                tracker.message.to_string(),
+                            ]
+                        }));
+                        print_rows(&rows);
+                    }
+                }
+            }
+        }
+        Command::ReplaceTrackers {
+            pattern,
+            replacement,
+            hash,
+            dry_run,
+        } => {
+            let target = hash.map(MultiTarget::Hash).unwrap_or(MultiTarget::All);
+            let replacement = TrackerReplacement::new(&pattern, &replacement)
+                .target(target)
+                .dry_run(dry_run);
+            let report = replace_trackers(&*client, &replacement).await?;
+
+            let mut rows = Vec::new();
+            let mut failed = 0;
+            for entry in &report.entries {
+                for edit in &entry.edits {
+                    rows.push(vec![
+                        entry.hash.to_string(),
+                        edit.from.to_string(),
+                        edit.to.to_string(),
+                    ]);
+                }
+                if let TrackerReplacementResult::Failed(e) = &entry.result {
+                    eprintln!("{} ({}): {e}", entry.name, entry.hash);
+                    failed += 1;
+                }
+            }
+            print_rows(&rows);
+            if failed > 0 {
+                return Err(format!("Failed to replace trackers of {failed} torrents").into());
+            }
+        }
+        Command::Files { hash, output } => {
+            let files = client.get_files(&hash).await?;
+            match output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&files)?),
+                Output::Table => {
+                    let mut rows = vec![vec!["SIZE".to_string(), "PATH".to_string()]];
+                    rows.extend(
+                        files.iter().map(|file| {
+                            vec![file.size.to_string(), file.path.display().to_string()]
+                        }),
+                    );
+                    print_rows(&rows);
+                }
+            }
+        }
+        Command::SetLocation { .. } => unreachable!("handled before connecting"),
+    }
+
+    Ok(())
+}
+
+/// Returns the connection profile with the command-line overrides
+fn profile(cli: &Cli) -> CliResult<Profile> {
+    let config = Config::load_from(cli.config.as_deref())?;
+
+    // The default profile does not need to exist when everything is given on the command-line
+    let mut profile = match config.profile(&cli.profile) {
+        Ok(profile) => profile.clone(),
+        Err(_) if cli.profile == "default" && cli.host.is_some() => Profile::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some(host) = &cli.host {
+        profile.url = Some(host.to_string());
+    }
+    if let Some(user) = &cli.user {
+        profile.user = Some(user.to_string());
+    }
+    if let Some(password) = &cli.password {
+        profile.password = Some(password.to_string());
+        profile.password_file = None;
+    }
+
+    Ok(profile)
+}
+
+async fn add(
+    client: &(dyn Api + 'static),
+    args: AddArgs,
+) -> CliResult<Option<hightorrent::InfoHash>> {
+    let mut add = client.add();
+
+    if let Some(save_path) = &args.save_path {
+        add = add.save_path(save_path);
+    }
+    if let Some(paused) = flag(args.paused, args.no_paused) {
+        add = add.paused(paused);
+    }
+    if let Some(tags) = args.tags {
+        add = add.tags(tags);
+    }
+    if let Some(category) = &args.category {
+        add = add.category(category);
+    }
+    if let Some(rename) = &args.rename {
+        add = add.rename(rename);
+    }
+    if let Some(skip_checking) = flag(args.skip_checking, args.no_skip_checking) {
+        add = add.skip_checking(skip_checking);
+    }
+    if let Some(content_layout) = args.content_layout {
+        add = add.content_layout(content_layout.into());
+    }
+    if let Some(stop_condition) = args.stop_condition {
+        add = add.stop_condition(stop_condition.into());
+    }
+    if let Some(auto_management) = flag(args.auto_management, args.no_auto_management) {
+        add = add.auto_management(auto_management);
+    }
+    if let Some(download_path) = &args.download_path {
+        add = add.download_path(download_path);
+    }
+    if let Some(upload_limit) = args.upload_limit {
+        add = add.upload_limit(upload_limit);
+    }
+    if let Some(download_limit) = args.download_limit {
+        add = add.download_limit(download_limit);
+    }
+    if let Some(ratio_limit) = args.ratio_limit {
+        add = add.ratio_limit(ratio_limit);
+    }
+    if let Some(seeding_time_limit) = args.seeding_time_limit {
+        add = add.seeding_time_limit(seeding_time_limit);
+    }
+    if let Some(sequential_download) = flag(args.sequential_download, args.no_sequential_download) {
+        add = add.sequential_download(sequential_download);
+    }
+    if let Some(first_last_piece_priority) = flag(
+        args.first_last_piece_priority,
+        args.no_first_last_piece_priority,
+    ) {
+        add = add.first_last_piece_priority(first_last_piece_priority);
+    }
+    if let Some(add_to_top_of_queue) = flag(args.add_to_top_of_queue, args.no_add_to_top_of_queue) {
+        add = add.add_to_top_of_queue(add_to_top_of_queue);
+    }
+    if let Some(wait) = args.wait {
+        add = add.wait(Duration::from_secs(wait));
+    }
+
+    let source = args.source.as_str();
+    let add = if source.starts_with("magnet:") {
+        add.magnet(source)
+    } else if source.starts_with("http://") || source.starts_with("https://") {
+        add.url(source)
+    } else if source.ends_with(".magnet") {
+        add.magnet_file(Path::new(source))
+    } else {
+        add.torrent_file(Path::new(source))
+    };
+
+    Ok(add.send().await?)
+}
+
+/// Returns the value of a `--flag`/`--no-flag` pair, or None when neither is given
+fn flag(enabled: bool, disabled: bool) -> Option<bool> {
+    match (enabled, disabled) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a seeding limit, which is `global`, `unlimited` or a value
+fn parse_limit<T, E>(s: &str, value: impl Fn(&str) -> Result<T, E>) -> Result<SeedingLimit<T>, E> {
+    match s {
+        "global" => Ok(SeedingLimit::Global),
+        "unlimited" => Ok(SeedingLimit::Unlimited),
+        _ => value(s).map(SeedingLimit::Limit),
+    }
+}
+
+fn parse_ratio_limit(s: &str) -> Result<SeedingLimit<f64>, std::num::ParseFloatError> {
+    parse_limit(s, str::parse)
+}
+
+fn parse_seeding_time_limit(s: &str) -> Result<SeedingLimit<Duration>, std::num::ParseIntError> {
+    parse_limit(s, |minutes| {
+        minutes
+            .parse::<u64>()
+            .map(|minutes| Duration::from_secs(minutes.saturating_mul(60)))
+    })
+}
+
+fn print_torrents(torrents: &[Torrent], output: Output) -> CliResult<()> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(torrents)?),
+        Output::Table => {
+            let mut rows = vec![vec![
+                "ID".to_string(),
+                "STATE".to_string(),
+                "PROGRESS".to_string(),
+                "NAME".to_string(),
+            ]];
+            rows.extend(torrents.iter().map(|torrent| {
+                vec![
+                    torrent.id.to_string(),
+                    torrent.state.to_string(),
+                    format!("{}%", torrent.progress),
+                    torrent.name.to_string(),
+                ]
+            }));
+            print_rows(&rows);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints rows as a table with aligned columns
+fn print_rows(rows: &[Vec<String>]) {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                if column + 1 == row.len() {
+                    // Don't pad the last column
+                    cell.to_string()
+                } else {
+                    format!("{cell:width$}", width = widths[column])
+                }
+            })
+            .collect();
+        println!("{}", line.join("  "));
+    }
+}
@@ -0,0 +1,346 @@
+//! Named connection profiles, loaded from a TOML file and from environment variables.
+//!
+//! The configuration file contains one table per profile:
+//!
+//! ```toml
+//! [profiles.default]
+//! backend = "qbittorrent"
+//! url = "http://localhost:8080"
+//! user = "admin"
+//! password_file = "/run/secrets/qbittorrent"
+//! # Timeouts are in seconds
+//! timeout = 30
+//! connect_timeout = 5
+//! proxy = "http://proxy.lan:3128"
+//! session_file = "/var/cache/hightorrent/session"
+//!
+//! [profiles.seedbox.tls]
+//! ca_cert = "/etc/ssl/seedbox.pem"
+//! client_cert = "/etc/ssl/hightorrent.pem"
+//! client_key = "/etc/ssl/hightorrent.key"
+//!
+//! [profiles.nas.tls]
+//! fingerprint = "3A:1F:...:9C"
+//! ```
+//!
+//! Every setting may be overridden with an environment variable named
+//! `HIGHTORRENT_PROFILE_{NAME}_{SETTING}`, such as `HIGHTORRENT_PROFILE_DEFAULT_PASSWORD`
+//! or `HIGHTORRENT_PROFILE_SEEDBOX_TLS_CA_CERT`. A profile may be defined entirely
+//! from the environment, as long as its URL is set.
+//!
+//! ```no_run
+//! use hightorrent_api::Api;
+//! use hightorrent_api::config::Config;
+//!
+//! # async fn run() -> Result<(), hightorrent_api::ApiError> {
+//! let config = Config::load()?;
+//! let client = config.profile("default")?.connect().await?;
+//!
+//! for torrent in client.list().await? {
+//!     println!("{}", torrent.name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Deserializer};
+use snafu::{OptionExt, ResultExt};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub use crate::api::Backend;
+use crate::api_error::*;
+use crate::{Api, ApiError};
+
+/// Prefix of the environment variables overriding profile settings
+pub const ENV_PREFIX: &str = "HIGHTORRENT_PROFILE_";
+
+/// The configuration file, containing named profiles
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Connection settings for a torrent client
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub backend: Backend,
+    pub url: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Read the password from this file, instead of `password`
+    pub password_file: Option<PathBuf>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Timeout for a whole request
+    #[serde(default, deserialize_with = "deserialize_secs")]
+    pub timeout: Option<Duration>,
+    /// Timeout for establishing the connection
+    #[serde(default, deserialize_with = "deserialize_secs")]
+    pub connect_timeout: Option<Duration>,
+    /// HTTP(S) or SOCKS5 proxy to reach the torrent client through
+    pub proxy: Option<String>,
+    /// Persist the session in this file, to avoid logging in on every run
+    pub session_file: Option<PathBuf>,
+}
+
+/// TLS settings for HTTPS connections to a torrent client
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Additional root certificate (PEM) to trust
+    pub ca_cert: Option<PathBuf>,
+    /// Only accept the server certificate with this SHA-256 fingerprint
+    pub fingerprint: Option<String>,
+    /// Client certificate (PEM) for mutual TLS
+    pub client_cert: Option<PathBuf>,
+    /// Private key (PKCS#8 PEM) of the client certificate
+    pub client_key: Option<PathBuf>,
+    /// Accept any certificate, including self-signed and expired ones
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+}
+
+fn deserialize_secs<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(d)?.map(Duration::from_secs))
+}
+
+impl FromStr for Config {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Config, ApiError> {
+        toml::from_str(s).boxed().context(InvalidConfigError)
+    }
+}
+
+impl Config {
+    /// Reads the configuration file at the given path, without environment overrides
+    pub fn from_file(path: &Path) -> Result<Config, ApiError> {
+        let content = std::fs::read_to_string(path).context(ConfigIoError {
+            path: path.to_path_buf(),
+        })?;
+        Config::from_str(&content)
+    }
+
+    /// Returns the default path of the configuration file, `$XDG_CONFIG_HOME/hightorrent/config.toml`,
+    /// falling back to `~/.config/hightorrent/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("hightorrent").join("config.toml"))
+    }
+
+    /// Reads the configuration file at the default path, if it exists, then applies
+    /// environment overrides.
+    pub fn load() -> Result<Config, ApiError> {
+        let path = Config::default_path().filter(|path| path.exists());
+        Config::load_from(path.as_deref())
+    }
+
+    /// Reads the configuration file at the given path, if any, then applies environment
+    /// overrides.
+    pub fn load_from(path: Option<&Path>) -> Result<Config, ApiError> {
+        let mut config = match path {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.merge_env(std::env::vars())?;
+        Ok(config)
+    }
+
+    /// Applies overrides from environment variables, in the form `HIGHTORRENT_PROFILE_{NAME}_{SETTING}`.
+    ///
+    /// Profile names are lowercased. Unknown settings are ignored.
+    pub fn merge_env<I: IntoIterator<Item = (String, String)>>(
+        &mut self,
+        vars: I,
+    ) -> Result<(), ApiError> {
+        for (key, value) in vars {
+            let Some(rest) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let Some((name, setting)) = split_env_setting(rest) else {
+                continue;
+            };
+
+            let profile = self.profiles.entry(name.to_lowercase()).or_default();
+            profile.set(setting, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the profile with the given name
+    pub fn profile(&self, name: &str) -> Result<&Profile, ApiError> {
+        self.profiles
+            .get(name)
+            .context(MissingProfileError { name })
+    }
+}
+
+/// Settings which can be overridden from the environment.
+///
+/// Longer settings come first, so `PASSWORD_FILE` is not mistaken for `PASSWORD`.
+const ENV_SETTINGS: &[&str] = &[
+    "TLS_ACCEPT_INVALID_CERTS",
+    "TLS_FINGERPRINT",
+    "TLS_CLIENT_CERT",
+    "TLS_CLIENT_KEY",
+    "TLS_CA_CERT",
+    "CONNECT_TIMEOUT",
+    "SESSION_FILE",
+    "PASSWORD_FILE",
+    "PASSWORD",
+    "BACKEND",
+    "TIMEOUT",
+    "PROXY",
+    "USER",
+    "URL",
+];
+
+/// Reads a file referenced by the configuration
+#[cfg(feature = "qbittorrent")]
+fn read_file(path: &Path) -> Result<Vec<u8>, ApiError> {
+    std::fs::read(path).context(ConfigIoError {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Parses a number of seconds from an environment variable
+fn parse_secs(setting: &str, value: &str) -> Result<Duration, ApiError> {
+    value
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| ApiError::InvalidConfig {
+            source: format!("{setting} must be a number of seconds, not {value}").into(),
+        })
+}
+
+/// Splits `{NAME}_{SETTING}` into its profile name and setting
+fn split_env_setting(s: &str) -> Option<(&str, &str)> {
+    ENV_SETTINGS.iter().find_map(|setting| {
+        let name = s.strip_suffix(setting)?.strip_suffix('_')?;
+        (!name.is_empty()).then_some((name, *setting))
+    })
+}
+
+impl Profile {
+    /// Overrides a setting from its environment variable name
+    fn set(&mut self, setting: &str, value: String) -> Result<(), ApiError> {
+        match setting {
+            "BACKEND" => self.backend = value.parse()?,
+            "URL" => self.url = Some(value),
+            "USER" => self.user = Some(value),
+            "PASSWORD" => self.password = Some(value),
+            "PASSWORD_FILE" => self.password_file = Some(PathBuf::from(value)),
+            "TIMEOUT" => self.timeout = Some(parse_secs(setting, &value)?),
+            "CONNECT_TIMEOUT" => self.connect_timeout = Some(parse_secs(setting, &value)?),
+            "PROXY" => self.proxy = Some(value),
+            "SESSION_FILE" => self.session_file = Some(PathBuf::from(value)),
+            "TLS_CA_CERT" => self.tls.ca_cert = Some(PathBuf::from(value)),
+            "TLS_FINGERPRINT" => self.tls.fingerprint = Some(value),
+            "TLS_CLIENT_CERT" => self.tls.client_cert = Some(PathBuf::from(value)),
+            "TLS_CLIENT_KEY" => self.tls.client_key = Some(PathBuf::from(value)),
+            "TLS_ACCEPT_INVALID_CERTS" => {
+                self.tls.accept_invalid_certs =
+                    value.parse().map_err(|_| ApiError::InvalidConfig {
+                        source: format!("{setting} must be true or false, not {value}").into(),
+                    })?
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Returns the password, reading it from `password_file` when set.
+    ///
+    /// Trailing newlines in the password file are ignored.
+    pub fn password(&self) -> Result<String, ApiError> {
+        match &self.password_file {
+            Some(path) => {
+                let password = std::fs::read_to_string(path).context(ConfigIoError {
+                    path: path.to_path_buf(),
+                })?;
+                Ok(password.trim_end_matches(['\r', '\n']).to_string())
+            }
+            None => Ok(self.password.clone().unwrap_or_default()),
+        }
+    }
+
+    /// Configures a qBittorrent client for this profile's URL, credentials, timeouts, proxy,
+    /// TLS settings and session file, without logging in
+    #[cfg(feature = "qbittorrent")]
+    pub fn qbittorrent_builder(
+        &self,
+    ) -> Result<crate::qbittorrent::QBittorrentClientBuilder, ApiError> {
+        let url = self.url.as_ref().ok_or_else(|| ApiError::InvalidConfig {
+            source: "Missing URL in profile".into(),
+        })?;
+        let mut builder = crate::QBittorrentClient::builder(
+            url,
+            self.user.as_deref().unwrap_or_default(),
+            &self.password()?,
+        );
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ca_cert) = &self.tls.ca_cert {
+            builder = builder.add_root_certificate(&read_file(ca_cert)?);
+        }
+        if let Some(fingerprint) = &self.tls.fingerprint {
+            builder = builder.certificate_fingerprint(fingerprint);
+        }
+        match (&self.tls.client_cert, &self.tls.client_key) {
+            (Some(cert), Some(key)) => {
+                builder = builder.client_certificate(&read_file(cert)?, &read_file(key)?);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(ApiError::InvalidConfig {
+                    source: "TLS client_cert and client_key must be set together".into(),
+                });
+            }
+        }
+        builder = builder.danger_accept_invalid_certs(self.tls.accept_invalid_certs);
+        if let Some(session_file) = &self.session_file {
+            builder =
+                builder.session_store(crate::qbittorrent::FileSessionStore::new(session_file));
+        }
+
+        Ok(builder)
+    }
+
+    /// Logs into the torrent client described by this profile
+    pub async fn connect(&self) -> Result<Box<dyn Api>, ApiError> {
+        match self.backend {
+            #[cfg(feature = "qbittorrent")]
+            Backend::QBittorrent => Ok(Box::new(self.connect_qbittorrent().await?)),
+            #[cfg(not(feature = "qbittorrent"))]
+            Backend::QBittorrent => Err(ApiError::UnsupportedBackend {
+                backend: "qbittorrent".to_string(),
+            }),
+        }
+    }
+
+    /// Logs into the qBittorrent client described by this profile, whatever its `backend`
+    #[cfg(feature = "qbittorrent")]
+    pub async fn connect_qbittorrent(&self) -> Result<crate::QBittorrentClient, ApiError> {
+        self.qbittorrent_builder()?.login().await
+    }
+}
@@ -23,9 +23,14 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! HTTPS connections require the `rustls` or `native-tls` feature flag, which also enable extra
+//! root certificates and client certificates. Pinning a self-signed certificate by its fingerprint
+//! requires `rustls`.
+//!
 //! # Supported backends
 //!
-//! - [x] QBittorrent (v5.0.x, v5.1.x)
+//! - [x] QBittorrent (v4.4.x to v5.1.x)
 //! - [ ] Transmission
 //!
 //! ## qBittorrent notes
@@ -34,8 +39,9 @@
 //!
 //! - v5.1.2 (2 July 2025)
 //! - v5.0.5 (13 April 2025)
+//! - v4.6.7
 //!
-//! qBittorrent v4.6.x is known not to work properly due to the ever changing API. Checking support in newer releases only requires changing the [CI configuration](.github/workflows/ci.yml) (pull requests welcome). We will not add support for older qBittorrent releases (Debian 13 Trixies packages qBittorrent v5.1.x), but contributions for this are welcome. Bittorrent v2 is only supported since v4.4.0 (6 January 2022) so it's unlikely we'll ever support an older release.
+//! Older qBittorrent releases since v4.4.0 are supported through a compatibility layer: the Web API version is detected at login, and endpoints, form fields and torrent states renamed in v5.0 (`paused`/`stopped`) are translated accordingly. Torrent states are always reported with their v5 names (`stoppedDL`, `stoppedUP`). Checking support in newer releases only requires changing the [CI configuration](.github/workflows/ci.yml) (pull requests welcome). Bittorrent v2 is only supported since v4.4.0 (6 January 2022) so it's unlikely we'll ever support an older release.
 //!
 //! The qBittorrent API is surprising (to say the least):
 //!
@@ -52,8 +58,32 @@
 //! - [x] List torrents
 //! - [x] Get torrent detailed information
 //! - [x] List, add, and remove trackers to a torrent
+//! - [x] Tracker details: tier, status, peer counts and messages
+//! - [x] Edit trackers, and replace tracker URLs across torrents
 //! - [x] Remove torrents
-//! - [x] Add torrents by magnet link or torrent file
+//! - [x] Add torrents by magnet link, torrent file, in-memory torrent or URL
+//! - [x] Add many torrents at once
+//! - [x] Export .torrent files
+//! - [x] Backup and restore torrents to/from a directory
+//! - [x] Migrate torrents between backends
+//! - [x] Reorder torrents in the download queue
+//! - [x] Set download behaviour (sequential, first/last piece priority, force start, super seeding)
+//! - [x] Load connection profiles from a configuration file and environment variables
+//! - [x] Select the backend at runtime, from a URL scheme such as `qbittorrent+https://`
+//! - [x] Query backend capabilities (operations, add options, Bittorrent v1/v2/hybrid)
+//! - [x] Retry transient failures with exponential backoff
+//! - [x] Configure timeouts, connection pool, headers and HTTP/SOCKS5 proxy
+//! - [x] HTTPS with custom root certificates, certificate pinning and client certificates
+//! - [x] Authenticate with a password, an API key, or bypassed authentication, behind a reverse proxy with Basic or bearer credentials
+//!
+//! # Command-line interface
+//!
+//! The `cli` feature flag builds the `hightorrent` binary, for everyday operations on torrent clients:
+//!
+//! ```sh
+//! cargo install hightorrent_api --features cli
+//! hightorrent --host http://localhost:8080 --user admin list --output json
+//! ```
 //!
 //! # Interacting with a torrent
 //!
@@ -96,6 +126,13 @@ pub use api::Api;
 pub mod api_error;
 pub use api_error::ApiError;
 
+pub mod backup;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod migrate;
+pub mod retry;
+pub mod trackers;
+
 #[cfg(feature = "qbittorrent")]
 pub mod qbittorrent;
 #[cfg(feature = "qbittorrent")]
@@ -0,0 +1,364 @@
+//! Migrate torrents from one [Api](crate::Api) backend to another.
+//!
+//! Torrents are copied with their metainfo, save path, tags, category, limits and trackers.
+//! The data itself is not moved, so both backends should see the same files at the same path.
+//!
+//! ```no_run
+//! use hightorrent_api::{Api, QBittorrentClient};
+//! use hightorrent_api::migrate::{Migration, migrate};
+//! use std::path::Path;
+//!
+//! # async fn run() -> Result<(), hightorrent_api::ApiError> {
+//! let source = QBittorrentClient::login("http://old:8080", "admin", "adminadmin").await?;
+//! let destination = QBittorrentClient::login("http://new:8080", "admin", "adminadmin").await?;
+//!
+//! let migration = Migration::new()
+//!     .filter(|torrent| torrent.tags.contains(&"linux".to_string()))
+//!     .remove_source(true)
+//!     .progress_log(Path::new("/var/lib/migration.log"));
+//!
+//! let report = migrate(&source, &destination, &migration).await?;
+//! for entry in report.entries {
+//!     println!("{}: {:?}", entry.hash, entry.result);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use hightorrent::{InfoHash, MultiTarget, SingleTarget, Torrent};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use tokio::io::AsyncWriteExt;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::api::AddSource;
+use crate::api_error::*;
+use crate::backup::{
+    BackupEntry, DEFAULT_ADD_TIMEOUT, RestoreDifference, add_entry, compare, complete_entry,
+    describe,
+};
+use crate::{Api, ApiError};
+
+/// A predicate selecting which torrents to migrate
+pub type MigrationFilter = Box<dyn Fn(&Torrent) -> bool + Send + Sync>;
+
+/// Settings for a migration between two backends
+pub struct Migration {
+    pub target: MultiTarget,
+    pub filter: Option<MigrationFilter>,
+    pub dry_run: bool,
+    pub remove_source: bool,
+    pub skip_checking: bool,
+    pub add_timeout: Duration,
+    pub progress_log: Option<PathBuf>,
+}
+
+impl Default for Migration {
+    fn default() -> Migration {
+        Migration {
+            target: MultiTarget::All,
+            filter: None,
+            dry_run: false,
+            remove_source: false,
+            skip_checking: false,
+            add_timeout: DEFAULT_ADD_TIMEOUT,
+            progress_log: None,
+        }
+    }
+}
+
+impl Migration {
+    pub fn new() -> Migration {
+        Migration::default()
+    }
+
+    /// Only migrate the targeted torrents
+    pub fn target(mut self, target: MultiTarget) -> Migration {
+        self.target = target;
+        self
+    }
+
+    /// Only migrate torrents for which the predicate returns true
+    pub fn filter<F: Fn(&Torrent) -> bool + Send + Sync + 'static>(mut self, f: F) -> Migration {
+        self.filter = Some(Box::new(f));
+        self
+    }
+
+    /// Report what would be migrated, without changing anything on either backend
+    pub fn dry_run(mut self, d: bool) -> Migration {
+        self.dry_run = d;
+        self
+    }
+
+    /// Remove torrents from the source backend (keeping the files) once they are verified on
+    /// the destination backend
+    pub fn remove_source(mut self, r: bool) -> Migration {
+        self.remove_source = r;
+        self
+    }
+
+    /// Skip checking existing files on the destination backend
+    pub fn skip_checking(mut self, s: bool) -> Migration {
+        self.skip_checking = s;
+        self
+    }
+
+    /// How long to wait for every torrent to appear in the destination backend, before
+    /// reading it back to add its trackers
+    pub fn add_timeout(mut self, t: Duration) -> Migration {
+        self.add_timeout = t;
+        self
+    }
+
+    /// Record progress in a file, so an interrupted migration can be resumed.
+    ///
+    /// Torrents recorded as migrated in this file are not added again. When removing torrents
+    /// from the source, those which were not removed yet are verified and removed.
+    pub fn progress_log(mut self, p: &Path) -> Migration {
+        self.progress_log = Some(p.to_path_buf());
+        self
+    }
+}
+
+/// The outcome of a migration, for every selected torrent
+#[derive(Debug)]
+pub struct MigrationReport {
+    pub entries: Vec<MigrationEntryReport>,
+}
+
+#[derive(Debug)]
+pub struct MigrationEntryReport {
+    pub hash: InfoHash,
+    pub name: String,
+    pub result: MigrationResult,
+    /// Differences between the source and destination torrents
+    pub differences: Vec<RestoreDifference>,
+    /// Whether the torrent was removed from the source backend
+    pub removed_from_source: bool,
+    /// Error recording the migrated torrent in the progress log, so it would be migrated again
+    /// when resuming
+    pub progress_log_error: Option<ApiError>,
+}
+
+#[derive(Debug)]
+pub enum MigrationResult {
+    /// The torrent would be migrated, but this is a dry run
+    DryRun,
+    Migrated,
+    /// The torrent was already added to the destination according to the progress log
+    AlreadyMigrated,
+    /// The destination backend already had this torrent, which was left untouched
+    Duplicate,
+    Failed(ApiError),
+}
+
+/// A line in the progress log, in JSON format.
+///
+/// A torrent is recorded once added to the destination backend, then again once removed from
+/// the source backend. The last line of a torrent is its current state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MigrationLogEntry {
+    pub hash: InfoHash,
+    /// Whether the torrent was removed from the source backend
+    pub removed_from_source: bool,
+}
+
+/// Copies torrents from the `source` backend to the `destination` backend.
+///
+/// Torrents which fail to migrate do not stop the migration; their error is in the report.
+/// A dry run also reports the torrents which the destination backend already has.
+pub async fn migrate<S: Api + ?Sized, D: Api + ?Sized>(
+    source: &S,
+    destination: &D,
+    migration: &Migration,
+) -> Result<MigrationReport, ApiError> {
+    let done = match &migration.progress_log {
+        Some(path) => read_progress_log(path).await?,
+        None => HashMap::new(),
+    };
+
+    let torrents = match &migration.target {
+        MultiTarget::All => source.list().await?.to_vec(),
+        MultiTarget::Hash(single_target) => match source.get(single_target).await? {
+            Some(torrent) => vec![torrent],
+            None => {
+                return Err(ApiError::MissingTorrent {
+                    hash: single_target.to_string(),
+                });
+            }
+        },
+    };
+
+    let existing = match migration.dry_run {
+        true => Some(destination.list().await?),
+        false => None,
+    };
+
+    let mut entries = Vec::new();
+    for torrent in torrents {
+        if let Some(filter) = &migration.filter
+            && !filter(&torrent)
+        {
+            continue;
+        }
+
+        let mut report = MigrationEntryReport {
+            hash: torrent.hash.clone(),
+            name: torrent.name.clone(),
+            result: MigrationResult::Migrated,
+            differences: Vec::new(),
+            removed_from_source: false,
+            progress_log_error: None,
+        };
+
+        if let Some(logged) = done.get(&torrent.hash) {
+            report.result = MigrationResult::AlreadyMigrated;
+            report.removed_from_source = logged.removed_from_source;
+            // The migration was interrupted before removing the torrent from the source
+            if migration.remove_source && !logged.removed_from_source && !migration.dry_run {
+                let resumed = match describe(source, torrent).await {
+                    Ok((entry, _)) => {
+                        verify_torrent(source, destination, migration, &entry, &mut report).await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = resumed {
+                    report.result = MigrationResult::Failed(e);
+                }
+            }
+        } else if let Some(existing) = &existing {
+            report.result = match existing.get(&SingleTarget::from(&torrent.hash)) {
+                Some(_) => MigrationResult::Duplicate,
+                None => MigrationResult::DryRun,
+            };
+        } else {
+            match migrate_torrent(source, destination, migration, torrent, &mut report).await {
+                Ok(()) => {}
+                Err(ApiError::DuplicateTorrent { .. }) => {
+                    report.result = MigrationResult::Duplicate;
+                }
+                Err(e) => {
+                    report.result = MigrationResult::Failed(e);
+                }
+            }
+        }
+
+        entries.push(report);
+    }
+
+    Ok(MigrationReport { entries })
+}
+
+/// Migrates a single torrent, recording in the report the differences found during
+/// verification, and whether the torrent was removed from the source backend.
+async fn migrate_torrent<S: Api + ?Sized, D: Api + ?Sized>(
+    source: &S,
+    destination: &D,
+    migration: &Migration,
+    torrent: Torrent,
+    report: &mut MigrationEntryReport,
+) -> Result<(), ApiError> {
+    let (entry, bytes) = describe(source, torrent).await?;
+
+    let add_source = match bytes {
+        Some(bytes) => AddSource::torrent_bytes(bytes),
+        None => AddSource::magnet(&entry.magnet()),
+    };
+    add_entry(destination, &entry, add_source, migration.skip_checking).await?;
+
+    // Record the torrent as soon as it is added, so a resumed migration does not report it
+    // as a duplicate when anything below fails
+    log_progress(migration, report).await;
+
+    verify_torrent(source, destination, migration, &entry, report).await
+}
+
+/// Completes a torrent added to the destination backend, compares it with the source, and
+/// removes it from the source when requested and both match exactly.
+async fn verify_torrent<S: Api + ?Sized, D: Api + ?Sized>(
+    source: &S,
+    destination: &D,
+    migration: &Migration,
+    entry: &BackupEntry,
+    report: &mut MigrationEntryReport,
+) -> Result<(), ApiError> {
+    complete_entry(destination, entry, migration.add_timeout).await?;
+
+    report.differences = compare(destination, entry).await?;
+    if migration.remove_source && report.differences.is_empty() {
+        source
+            .remove(&SingleTarget::from(&entry.hash), false)
+            .await?;
+        report.removed_from_source = true;
+        log_progress(migration, report).await;
+    }
+
+    Ok(())
+}
+
+/// Records the state of a torrent in the progress log, if any.
+///
+/// The torrent was migrated anyway, so failing to write the log does not stop the migration,
+/// and the first error is kept in the report.
+async fn log_progress(migration: &Migration, report: &mut MigrationEntryReport) {
+    let Some(path) = &migration.progress_log else {
+        return;
+    };
+
+    let entry = MigrationLogEntry {
+        hash: report.hash.clone(),
+        removed_from_source: report.removed_from_source,
+    };
+    if let Err(e) = write_progress_log(path, &entry).await {
+        report.progress_log_error.get_or_insert(e);
+    }
+}
+
+/// Reads the state of already-migrated torrents from the progress log, keeping the last line
+/// of every torrent.
+///
+/// A missing progress log means nothing was migrated yet.
+async fn read_progress_log(path: &Path) -> Result<HashMap<InfoHash, MigrationLogEntry>, ApiError> {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => {
+            return Err(e).context(MigrationLogError {
+                path: path.to_path_buf(),
+            });
+        }
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<MigrationLogEntry>(line)
+                .map(|entry| (entry.hash.clone(), entry))
+                .context(DeserializationError)
+        })
+        .collect()
+}
+
+/// Appends an entry to the progress log
+async fn write_progress_log(path: &Path, entry: &MigrationLogEntry) -> Result<(), ApiError> {
+    let mut line = serde_json::to_string(entry).context(SerializationError)?;
+    line.push('\n');
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .context(MigrationLogError {
+            path: path.to_path_buf(),
+        })?;
+    file.write_all(line.as_bytes())
+        .await
+        .context(MigrationLogError {
+            path: path.to_path_buf(),
+        })
+}
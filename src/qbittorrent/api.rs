@@ -1,28 +1,81 @@
+use bytes::Bytes;
 use hightorrent::{
     InfoHash, MultiTarget, SingleTarget, ToTorrent, ToTorrentContent, Torrent, TorrentContent,
     TorrentID, TorrentList, Tracker, TryIntoTracker,
 };
+use reqwest::cookie::Jar;
+use reqwest::header::{COOKIE, SET_COOKIE};
 use reqwest::multipart::Form;
 use reqwest::multipart::Part;
-use reqwest::{Client, ClientBuilder, Response, StatusCode, Url};
+use reqwest::{Body, Client, RequestBuilder, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
 use snafu::ResultExt;
-use tokio::{fs::File, io::AsyncReadExt};
+use tokio::fs::File;
+use tokio::sync::Mutex;
+use tokio::time::{Instant, sleep};
+use tokio_util::io::ReaderStream;
 
 use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
 
+use super::auth::authorize;
 use crate::{
     api::*,
     api_error::{ApiError as Error, *},
-    qbittorrent::{QBittorrentTorrent, QBittorrentTorrentContent, QBittorrentTracker},
+    qbittorrent::{
+        Auth, HttpAuth, QBittorrentClientBuilder, QBittorrentTorrent, QBittorrentTorrentContent,
+        QBittorrentTracker, QBittorrentVersion, SessionStore, torrent::tracker_details,
+    },
+    retry::RetryPolicy,
 };
 
 #[derive(Clone, Debug)]
 pub struct QBittorrentClient {
     host: String,
+    /// The `/api/v2/` URL on the host, validated at construction
+    api_url: Url,
     user: String,
     password: String,
     client: Client,
+    /// The cookie store of `client`, when it was built by this crate. Other HTTP clients are sent
+    /// the session cookie in an explicit header, overriding their own cookies.
+    cookies: Option<Arc<Jar>>,
+    /// Versions detected at login, shared between clones
+    version: Arc<RwLock<Option<QBittorrentVersion>>>,
+    session: Arc<Session>,
+    session_store: Option<Arc<dyn SessionStore>>,
+    retry: RetryPolicy,
+    /// Overrides the default timeout of `api_add_batch` checks
+    batch_check_timeout: Option<Duration>,
+    auth: Auth,
+    http_auth: Option<HttpAuth>,
+}
+
+/// Login state shared between clones of a client
+#[derive(Debug, Default)]
+struct Session {
+    /// Incremented on every successful login
+    generation: AtomicU64,
+    /// Held while logging in, so concurrent callers wait for a single login
+    login: Mutex<()>,
+    /// Session cookie, kept so it can be persisted in a SessionStore
+    sid: RwLock<Option<String>>,
+}
+
+impl Session {
+    fn sid(&self) -> Option<String> {
+        self.sid
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn set_sid(&self, sid: String) {
+        *self.sid.write().unwrap_or_else(PoisonError::into_inner) = Some(sid);
+    }
 }
 
 impl QBittorrentClient {
@@ -30,55 +83,168 @@ impl QBittorrentClient {
     ///
     /// Then perform `QBittorrentClient::do_login` to actually login.
     pub fn new_not_logged_in(host: &str, user: &str, password: &str) -> Result<Self, Error> {
-        let client = ClientBuilder::new()
-            .cookie_store(true)
-            .build()
-            .boxed()
-            .context(ClientInitError)?;
+        Self::builder(host, user, password).build()
+    }
+
+    /// Configures a new client: timeouts, connection pool, headers, proxy...
+    pub fn builder(host: &str, user: &str, password: &str) -> QBittorrentClientBuilder {
+        QBittorrentClientBuilder::new(host, user, password)
+    }
 
+    /// Create a new client that's not logged in yet, using a custom HTTP client.
+    ///
+    /// Fails with `ApiError::InvalidHost` when the host is not an HTTP(S) URL.
+    pub fn from_client(
+        host: &str,
+        user: &str,
+        password: &str,
+        client: Client,
+    ) -> Result<Self, Error> {
         Ok(Self {
             host: host.to_string(),
+            api_url: api_url(host)?,
             user: user.to_string(),
             password: password.to_string(),
             client,
+            cookies: None,
+            version: Arc::new(RwLock::new(None)),
+            session: Arc::new(Session::default()),
+            session_store: None,
+            retry: RetryPolicy::none(),
+            batch_check_timeout: None,
+            auth: Auth::default(),
+            http_auth: None,
         })
     }
 
+    /// Sets the cookie store of the HTTP client built by the builder, so the session cookie is
+    /// sent along with the other cookies
+    pub(crate) fn cookie_jar(mut self, jar: Arc<Jar>) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    /// Sets how to authenticate, validated by the builder
+    pub(crate) fn authentication(mut self, auth: Auth, http_auth: Option<HttpAuth>) -> Self {
+        self.auth = auth;
+        self.http_auth = http_auth;
+        self
+    }
+
+    /// Retries operations failing with a transient error, according to the policy.
+    ///
+    /// Operations adding/removing torrents or changing their settings are only retried when
+    /// the policy allows retrying non-idempotent operations.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// How long to wait for the torrents added in a batch to appear in the list of torrents.
+    ///
+    /// Torrents still missing after this timeout are reported as
+    /// `BatchAddResult::NotYetVisible`. By default, the timeout grows with the number of
+    /// torrents sent, as qBittorrent takes longer to load many torrents.
+    pub fn batch_check_timeout(mut self, timeout: Duration) -> Self {
+        self.batch_check_timeout = Some(timeout);
+        self
+    }
+
+    /// Runs an operation according to the retry policy
+    pub async fn _retry<T, F, Fut>(&self, idempotent: bool, operation: F) -> Result<T, Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.retry.run(idempotent, operation).await
+    }
+
+    /// Persists the session in a store, so it is reused by `do_login` in later runs.
+    ///
+    /// The stored session is validated by the first request, and the client only logs in
+    /// again when it expired.
+    pub fn session_store<S: SessionStore + 'static>(mut self, store: S) -> Self {
+        self.session_store = Some(Arc::new(store));
+        self
+    }
+
     pub async fn do_login(&self) -> Result<(), Error> {
-        let form = Form::new()
-            .text("username", self.user.to_string())
-            .text("password", self.password.to_string());
+        if !self.auth.uses_login() {
+            // Negotiating the version checks qBittorrent accepts requests without a session
+            self.negotiate().await?;
+            return Ok(());
+        }
 
-        let res = self
-            .client
-            .post(format!("{}/api/v2/auth/login", self.host))
-            .multipart(form)
-            .send()
-            .await
-            .boxed()
-            .context(HttpError)?;
+        let stored = match &self.session_store {
+            Some(store) => store.load().await?,
+            None => None,
+        };
 
-        if res.headers().get("set-cookie").is_some() {
-            Ok(())
-        } else {
-            Err(Error::InvalidLogin {
-                host: self.host.to_string(),
-                user: self.user.to_string(),
-            })
+        match stored {
+            // Negotiating the version validates the stored session,
+            // logging in again if it expired
+            Some(sid) => self._set_sid(&sid),
+            None => self.reconnect().await?,
         }
+
+        self.negotiate().await?;
+        Ok(())
     }
 
     /// Returns the qBittorrent version, in a `vX.Y.Z` format.
     pub async fn qbittorrent_version(&self) -> Result<String, Error> {
-        let res = self._get(self._endpoint("app/version")).await?;
-        let bytes = res.bytes().await.boxed().context(HttpError)?;
-        Ok(String::from_utf8_lossy(&bytes).to_string())
+        self._get_text(self._endpoint("app/version")).await
+    }
+
+    /// Returns the qBittorrent Web API version, in a `X.Y.Z` format.
+    pub async fn webapi_version(&self) -> Result<String, Error> {
+        self._get_text(self._endpoint("app/webapiVersion")).await
+    }
+
+    /// Returns the plaintext response to a GET request
+    async fn _get_text(&self, endpoint: Url) -> Result<String, Error> {
+        self._retry(true, || async {
+            let res = check(self._get(endpoint.clone()).await?, None).await?;
+            let bytes = res.bytes().await.map_err(request_error)?;
+            Ok(String::from_utf8_lossy(&bytes).to_string())
+        })
+        .await
+    }
+
+    /// Returns the parsed qBittorrent and Web API versions.
+    pub async fn version(&self) -> Result<QBittorrentVersion, Error> {
+        Ok(QBittorrentVersion {
+            app: self.qbittorrent_version().await?.parse()?,
+            webapi: self.webapi_version().await?.parse()?,
+        })
+    }
+
+    /// Detects the qBittorrent and Web API versions, to pick the right endpoints and fields.
+    ///
+    /// Called on login, so it only needs to be called again when the daemon was upgraded.
+    pub async fn negotiate(&self) -> Result<QBittorrentVersion, Error> {
+        let version = self.version().await?;
+        *self.version.write().unwrap_or_else(PoisonError::into_inner) = Some(version);
+        Ok(version)
+    }
+
+    /// Returns the versions detected at login, detecting them now if needed.
+    pub async fn negotiated_version(&self) -> Result<QBittorrentVersion, Error> {
+        let version = *self.version.read().unwrap_or_else(PoisonError::into_inner);
+        match version {
+            Some(version) => Ok(version),
+            None => self.negotiate().await,
+        }
     }
 
     /// Returns the URL to an endpoint without params
     pub fn _endpoint(&self, path: &str) -> Url {
-        Url::parse(&format!("{}/api/v2/{}", self.host, path))
-            .expect("PROGRAMMING ERROR: invalid api URL")
+        let mut url = self.api_url.clone();
+        // The API URL was checked to have a path when building the client
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(path.split('/'));
+        }
+        url
     }
 
     /// Returns the URL to an endpoint with custom query params
@@ -89,84 +255,158 @@ impl QBittorrentClient {
         V: AsRef<str>,
         <I as IntoIterator>::Item: Borrow<(K, V)>,
     {
-        Url::parse_with_params(&format!("{}/api/v2/{}", self.host, endpoint), args)
-            .expect("PROGRAMMING ERROR: invalid api URL")
+        let mut url = self._endpoint(endpoint);
+        url.query_pairs_mut().extend_pairs(args);
+        url
     }
 
-    pub async fn _post_multipart(&self, endpoint: Url, form: Form) -> Result<Response, Error> {
-        self.keepalive().await?;
-        self.client
-            .post(endpoint)
-            .multipart(form)
-            .send()
+    /// Sends a multipart POST request.
+    ///
+    /// The form is built by a closure, so the request can be replayed after logging in again.
+    pub async fn _post_multipart<F>(&self, endpoint: Url, form: F) -> Result<Response, Error>
+    where
+        F: Fn() -> Result<Form, Error> + Send + Sync,
+    {
+        self._send(|| async { Ok(self.client.post(endpoint.clone()).multipart(form()?)) })
             .await
-            .boxed()
-            .context(HttpError)
     }
 
     pub async fn _post(&self, endpoint: Url) -> Result<Response, Error> {
-        self.keepalive().await?;
-        self.client
-            .post(endpoint)
-            .send()
+        self._send(|| async { Ok(self.client.post(endpoint.clone())) })
             .await
-            .boxed()
-            .context(HttpError)
     }
 
     pub async fn _get(&self, endpoint: Url) -> Result<Response, Error> {
-        self.keepalive().await?;
-        self.client
-            .get(endpoint)
-            .send()
+        self._send(|| async { Ok(self.client.get(endpoint.clone())) })
             .await
-            .boxed()
-            .context(HttpError)
     }
 
-    /// Keeps the current session alive even if QBittorrent restarted
+    /// Sends the request built by the async closure.
     ///
-    /// Called before making a "real" API call in other methods. Not ideal because it now takes
-    /// two round-trips (3 when reconnecting), but better than failing because cookie expired.
-    pub async fn keepalive(&self) -> Result<(), Error> {
+    /// When QBittorrent answers 403 Forbidden because the session expired (for example when
+    /// QBittorrent restarted), logs in again and replays the request once. Without a session
+    /// (authentication bypassed or API key), 403 Forbidden fails with `ApiError::InvalidLogin`.
+    async fn _send<F, Fut>(&self, request: F) -> Result<Response, Error>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<RequestBuilder, Error>>,
+    {
+        let generation = self.session.generation.load(Ordering::Acquire);
         let res = self
-            .client
-            .get(self._endpoint("app/version"))
+            ._authorize(request().await?)
             .send()
             .await
-            .boxed()
-            .context(HttpError)?;
-        if res.status() == StatusCode::FORBIDDEN {
-            // We have been disconnected. Reconnect now!
-            // TODO: check if reconnect was successful?
-            self.reconnect().await?;
+            .map_err(request_error)?;
+        if res.status() != StatusCode::FORBIDDEN {
+            return Ok(res);
+        }
+        if !self.auth.uses_login() {
+            return Err(self._invalid_login());
         }
 
+        self._relogin(generation).await?;
+        self._authorize(request().await?)
+            .send()
+            .await
+            .map_err(request_error)
+    }
+
+    /// Adds the credentials to a request, and the session cookie when the HTTP client
+    /// has no cookie store of ours
+    fn _authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = authorize(request, &self.auth, self.http_auth.as_ref());
+        match (&self.cookies, self.session.sid()) {
+            (None, Some(sid)) => request.header(COOKIE, format!("SID={sid}")),
+            _ => request,
+        }
+    }
+
+    /// Sets the session cookie, also in the cookie store so it's sent with the other cookies
+    fn _set_sid(&self, sid: &str) {
+        if let Some(jar) = &self.cookies {
+            jar.add_cookie_str(&format!("SID={sid}; Path=/"), &self.api_url);
+        }
+        self.session.set_sid(sid.to_string());
+    }
+
+    /// Logs in again after the session from `generation` expired.
+    ///
+    /// Concurrent callers wait for a single login: when another caller logged in since
+    /// `generation`, the new session is reused.
+    async fn _relogin(&self, generation: u64) -> Result<(), Error> {
+        let _login = self.session.login.lock().await;
+        if self.session.generation.load(Ordering::Acquire) == generation {
+            self._login().await?;
+        }
+        Ok(())
+    }
+
+    /// Checks the session is still valid, logging in again if it expired.
+    pub async fn keepalive(&self) -> Result<(), Error> {
+        self._get(self._endpoint("app/version")).await?;
         Ok(())
     }
 
-    /// Triggers a reconnection to the QBittorrent API.
+    /// Logs in again to the QBittorrent API, replacing the current session.
     ///
-    // TODO: check if reconnect was successful?
+    /// Fails with `ApiError::InvalidLogin` when QBittorrent refuses the credentials.
+    /// Does nothing when the client does not log in (authentication bypassed or API key).
     pub async fn reconnect(&self) -> Result<(), Error> {
+        if !self.auth.uses_login() {
+            return Ok(());
+        }
+
+        let _login = self.session.login.lock().await;
+        self._login().await
+    }
+
+    /// Performs the actual login. Callers must hold the session login lock.
+    async fn _login(&self) -> Result<(), Error> {
         let form = Form::new()
             .text("username", self.user.to_string())
             .text("password", self.password.to_string());
 
-        let _ = self
-            .client
-            .post(self._endpoint("auth/login"))
-            .multipart(form)
-            .send()
-            .await
-            .boxed()
-            .context(HttpError)?;
+        let res = authorize(
+            self.client.post(self._endpoint("auth/login")),
+            &self.auth,
+            self.http_auth.as_ref(),
+        )
+        .multipart(form)
+        .send()
+        .await
+        .map_err(request_error)?;
+        // QBittorrent answers 403 Forbidden when the IP address is banned
+        let res = check(res, None).await?;
 
-        Ok(())
+        let sid = res
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(|cookie| cookie.strip_prefix("SID="))
+            .and_then(|cookie| cookie.split(';').next());
+
+        if let Some(sid) = sid {
+            self._set_sid(sid);
+            self.session.generation.fetch_add(1, Ordering::AcqRel);
+            if let Some(store) = &self.session_store {
+                store.save(sid).await?;
+            }
+            Ok(())
+        } else {
+            Err(self._invalid_login())
+        }
+    }
+
+    fn _invalid_login(&self) -> Error {
+        Error::InvalidLogin {
+            host: self.host.to_string(),
+            user: self.user.to_string(),
+        }
     }
 
     pub async fn _json<U: DeserializeOwned>(&self, res: Response) -> Result<U, Error> {
-        let full = res.bytes().await.boxed().context(HttpError)?;
+        let full = res.bytes().await.map_err(request_error)?;
         serde_json::from_slice(&full).context(DeserializationError)
     }
 
@@ -200,8 +440,16 @@ impl QBittorrentClient {
 
     /// Returns a list of torrents as a vector of a custom type
     pub async fn list_as<T: DeserializeOwned + AsRef<InfoHash>>(&self) -> Result<Vec<T>, Error> {
-        let res = self._get(self._endpoint("torrents/info")).await?;
-        self._json(res).await
+        self._get_json(self._endpoint("torrents/info")).await
+    }
+
+    /// Returns the JSON response to a GET request
+    async fn _get_json<U: DeserializeOwned>(&self, endpoint: Url) -> Result<U, Error> {
+        self._retry(true, || async {
+            let res = check(self._get(endpoint.clone()).await?, None).await?;
+            self._json(res).await
+        })
+        .await
     }
 
     /// Returns a single torrent as a custom type
@@ -217,11 +465,18 @@ impl QBittorrentClient {
 
     pub async fn set_location(&self, target: &SingleTarget, location: &str) -> Result<(), Error> {
         if let Some(id) = self.id(target).await? {
-            let form = Form::new()
-                .text("hashes", id.to_string())
-                .text("location", location.to_string());
-            self._post_multipart(self._endpoint("torrents/setLocation"), form)
-                .await?;
+            let form = || {
+                Ok(Form::new()
+                    .text("hashes", id.to_string())
+                    .text("location", location.to_string()))
+            };
+            self._retry(false, || async {
+                let res = self
+                    ._post_multipart(self._endpoint("torrents/setLocation"), form)
+                    .await?;
+                check(res, Some(target.to_string())).await
+            })
+            .await?;
 
             Ok(())
         } else {
@@ -230,6 +485,316 @@ impl QBittorrentClient {
             })
         }
     }
+
+    /// Returns the `hashes` form value for the requested MultiTarget
+    pub async fn _hashes(&self, target: &MultiTarget) -> Result<String, Error> {
+        match target {
+            MultiTarget::All => Ok("all".to_string()),
+            MultiTarget::Hash(single_target) => match self.id(single_target).await? {
+                Some(id) => Ok(id.to_string()),
+                None => Err(Error::MissingTorrent {
+                    hash: single_target.to_string(),
+                }),
+            },
+        }
+    }
+
+    /// Moves the targeted torrents one position up in the queue.
+    ///
+    /// Fails with `ApiError::QueueingDisabled` when queueing is disabled in QBittorrent.
+    pub async fn increase_priority(&self, target: &MultiTarget) -> Result<(), Error> {
+        self._queue("torrents/increasePrio", target).await
+    }
+
+    /// Moves the targeted torrents one position down in the queue.
+    ///
+    /// Fails with `ApiError::QueueingDisabled` when queueing is disabled in QBittorrent.
+    pub async fn decrease_priority(&self, target: &MultiTarget) -> Result<(), Error> {
+        self._queue("torrents/decreasePrio", target).await
+    }
+
+    /// Moves the targeted torrents to the top of the queue.
+    ///
+    /// Fails with `ApiError::QueueingDisabled` when queueing is disabled in QBittorrent.
+    pub async fn top_priority(&self, target: &MultiTarget) -> Result<(), Error> {
+        self._queue("torrents/topPrio", target).await
+    }
+
+    /// Moves the targeted torrents to the bottom of the queue.
+    ///
+    /// Fails with `ApiError::QueueingDisabled` when queueing is disabled in QBittorrent.
+    pub async fn bottom_priority(&self, target: &MultiTarget) -> Result<(), Error> {
+        self._queue("torrents/bottomPrio", target).await
+    }
+
+    /// Stops (pauses) the targeted torrents.
+    pub async fn stop(&self, target: &MultiTarget) -> Result<(), Error> {
+        let endpoint = self.negotiated_version().await?.stop_endpoint();
+        self._start_stop(endpoint, target).await
+    }
+
+    /// Starts (resumes) the targeted torrents.
+    pub async fn start(&self, target: &MultiTarget) -> Result<(), Error> {
+        let endpoint = self.negotiated_version().await?.start_endpoint();
+        self._start_stop(endpoint, target).await
+    }
+
+    async fn _start_stop(&self, endpoint: &str, target: &MultiTarget) -> Result<(), Error> {
+        let hashes = self._hashes(target).await?;
+        let form = || Ok(Form::new().text("hashes", hashes.clone()));
+        self._retry(false, || async {
+            let res = self._post_multipart(self._endpoint(endpoint), form).await?;
+            check(res, Some(target_string(target))).await
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn _queue(&self, endpoint: &str, target: &MultiTarget) -> Result<(), Error> {
+        let hashes = self._hashes(target).await?;
+        let form = || Ok(Form::new().text("hashes", hashes.clone()));
+        self._retry(false, || async {
+            let res = self._post_multipart(self._endpoint(endpoint), form).await?;
+            if res.status() == StatusCode::CONFLICT {
+                return Err(Error::QueueingDisabled);
+            }
+            check(res, Some(target_string(target))).await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the raw QBittorrent trackers of a torrent, including DHT/PeX/LSD
+    pub async fn _trackers_raw(
+        &self,
+        target: &SingleTarget,
+    ) -> Result<Vec<QBittorrentTracker>, Error> {
+        let truncated = target.truncated();
+        self._retry(true, || async {
+            let res = self
+                ._post(self._endpoint_params("torrents/trackers", vec![("hash", truncated)]))
+                .await?;
+            let res = check(res, Some(target.to_string())).await?;
+            self._json(res).await
+        })
+        .await
+    }
+
+    /// Returns the raw QBittorrent torrents matching the requested MultiTarget
+    pub async fn _list_target_raw(
+        &self,
+        target: &MultiTarget,
+    ) -> Result<Vec<QBittorrentTorrent>, Error> {
+        let torrents: Vec<QBittorrentTorrent> =
+            self._get_json(self._endpoint("torrents/info")).await?;
+
+        match target {
+            MultiTarget::All => Ok(torrents),
+            MultiTarget::Hash(single_target) => {
+                let matching: Vec<QBittorrentTorrent> = torrents
+                    .into_iter()
+                    .filter(|torrent| single_target.matches_hash(&torrent.hash()))
+                    .collect();
+                if matching.is_empty() {
+                    Err(Error::MissingTorrent {
+                        hash: single_target.to_string(),
+                    })
+                } else {
+                    Ok(matching)
+                }
+            }
+        }
+    }
+
+    /// Enables or disables sequential download for the targeted torrents.
+    pub async fn set_sequential_download(
+        &self,
+        target: &MultiTarget,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        self._set_flag(
+            target,
+            enabled,
+            |torrent| torrent.sequential_download,
+            "torrents/toggleSequentialDownload",
+            None,
+        )
+        .await
+    }
+
+    /// Enables or disables first/last piece priority for the targeted torrents.
+    pub async fn set_first_last_piece_priority(
+        &self,
+        target: &MultiTarget,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        self._set_flag(
+            target,
+            enabled,
+            |torrent| torrent.first_last_piece_priority,
+            "torrents/toggleFirstLastPiecePrio",
+            None,
+        )
+        .await
+    }
+
+    /// Enables or disables force start for the targeted torrents.
+    pub async fn set_force_start(&self, target: &MultiTarget, enabled: bool) -> Result<(), Error> {
+        self._set_flag(
+            target,
+            enabled,
+            |torrent| torrent.force_start,
+            "torrents/setForceStart",
+            Some("value"),
+        )
+        .await
+    }
+
+    /// Enables or disables super seeding for the targeted torrents.
+    pub async fn set_super_seeding(
+        &self,
+        target: &MultiTarget,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        self._set_flag(
+            target,
+            enabled,
+            |torrent| torrent.super_seeding,
+            "torrents/setSuperSeeding",
+            Some("value"),
+        )
+        .await
+    }
+
+    /// Enables or disables automatic torrent management for the targeted torrents.
+    pub async fn set_auto_management(
+        &self,
+        target: &MultiTarget,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        self._set_flag(
+            target,
+            enabled,
+            |torrent| torrent.auto_management,
+            "torrents/setAutoManagement",
+            Some("enable"),
+        )
+        .await
+    }
+
+    /// Sets a boolean flag on the targeted torrents.
+    ///
+    /// Some QBittorrent endpoints can only toggle a flag, so the current state is read first
+    /// and only torrents which don't have the desired state are sent to the endpoint. When
+    /// `value_field` is set, the desired state is also sent in this form field.
+    async fn _set_flag(
+        &self,
+        target: &MultiTarget,
+        enabled: bool,
+        flag: fn(&QBittorrentTorrent) -> bool,
+        endpoint: &str,
+        value_field: Option<&'static str>,
+    ) -> Result<(), Error> {
+        let hashes: Vec<String> = self
+            ._list_target_raw(target)
+            .await?
+            .iter()
+            .filter(|torrent| flag(torrent) != enabled)
+            .map(|torrent| torrent.id.to_string())
+            .collect();
+
+        if hashes.is_empty() {
+            return Ok(());
+        }
+
+        let form = || {
+            let mut form = Form::new().text("hashes", hashes.join("|"));
+            if let Some(value_field) = value_field {
+                form = form.text(value_field, enabled.to_string());
+            }
+            Ok(form)
+        };
+
+        self._retry(false, || async {
+            let res = self._post_multipart(self._endpoint(endpoint), form).await?;
+            check(res, Some(target_string(target))).await
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// Returns the `/api/v2/` URL on the host, which may have a path (behind a reverse proxy)
+fn api_url(host: &str) -> Result<Url, Error> {
+    let invalid = || Error::InvalidHost {
+        host: host.to_string(),
+    };
+
+    let mut url = Url::parse(host).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(invalid());
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    url.path_segments_mut()
+        .map_err(|_| invalid())?
+        .pop_if_empty()
+        .extend(["api", "v2", ""]);
+    Ok(url)
+}
+
+/// Returns an error for a failed request, telling timeouts apart
+fn request_error(e: reqwest::Error) -> Error {
+    if e.is_timeout() {
+        Error::Timeout {
+            source: Box::new(e),
+        }
+    } else {
+        Error::Http {
+            source: Box::new(e),
+        }
+    }
+}
+
+/// Returns the response when successful, or the error matching its status, along with the
+/// message from QBittorrent.
+///
+/// For endpoints working on a torrent, 404 Not Found means the torrent `hash` is missing.
+/// Otherwise, it means the endpoint does not exist in this QBittorrent release.
+async fn check(res: Response, hash: Option<String>) -> Result<Response, Error> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    let endpoint = res.url().path().to_string();
+    let message = res.text().await.unwrap_or_default();
+    Err(match (status, hash) {
+        (StatusCode::NOT_FOUND, Some(hash)) => Error::MissingTorrent { hash },
+        (StatusCode::NOT_FOUND, None) | (StatusCode::METHOD_NOT_ALLOWED, _) => {
+            Error::UnsupportedOperation { endpoint, message }
+        }
+        (StatusCode::BAD_REQUEST | StatusCode::UNSUPPORTED_MEDIA_TYPE, _) => {
+            Error::InvalidArgument { message }
+        }
+        (StatusCode::FORBIDDEN, _) if message.to_lowercase().contains("banned") => {
+            Error::Banned { message }
+        }
+        (StatusCode::FORBIDDEN, _) => Error::Forbidden { message },
+        (StatusCode::CONFLICT, _) => Error::Conflict { message },
+        _ => Error::Server {
+            status: status.as_u16(),
+            body: message,
+        },
+    })
+}
+
+/// Stringy representation of a MultiTarget for error reporting
+fn target_string(target: &MultiTarget) -> String {
+    match target {
+        MultiTarget::All => "all".to_string(),
+        MultiTarget::Hash(single_target) => single_target.to_string(),
+    }
 }
 
 #[async_trait]
@@ -252,9 +817,13 @@ impl Api for QBittorrentClient {
         Ok(api_client)
     }
 
+    async fn capabilities(&self) -> Result<Capabilities, Error> {
+        Ok(self.negotiated_version().await?.capabilities())
+    }
+
     async fn list(&self) -> Result<TorrentList, Error> {
-        let res = self._get(self._endpoint("torrents/info")).await?;
-        let concrete: Vec<QBittorrentTorrent> = self._json(res).await?;
+        let concrete: Vec<QBittorrentTorrent> =
+            self._get_json(self._endpoint("torrents/info")).await?;
         Ok(concrete.iter().map(|t| t.to_torrent()).collect())
     }
 
@@ -264,25 +833,27 @@ impl Api for QBittorrentClient {
 
     async fn remove(&self, target: &SingleTarget, delete_files: bool) -> Result<(), Error> {
         if let Some(id) = self.id(target).await? {
-            let mut form = Form::new();
-            form = form.text("hashes", id.as_str().to_string());
-            form = form.text("deleteFiles", delete_files.to_string());
+            let form = || {
+                Ok(Form::new()
+                    .text("hashes", id.as_str().to_string())
+                    .text("deleteFiles", delete_files.to_string()))
+            };
 
-            self._post_multipart(self._endpoint("torrents/delete"), form)
-                .await?;
+            self._retry(false, || async {
+                let res = self
+                    ._post_multipart(self._endpoint("torrents/delete"), form)
+                    .await?;
+                check(res, Some(target.to_string())).await
+            })
+            .await?;
         }
 
         Ok(())
     }
 
     async fn get_trackers(&self, target: &SingleTarget) -> Result<Vec<Tracker>, Error> {
-        let truncated = target.truncated();
-        let res = self
-            ._post(self._endpoint_params("torrents/trackers", vec![("hash", truncated)]))
-            .await?;
-
         let trackers = self
-            ._json::<Vec<QBittorrentTracker>>(res)
+            ._trackers_raw(target)
             .await?
             .into_iter()
             .filter_map(|tracker| {
@@ -294,44 +865,81 @@ impl Api for QBittorrentClient {
         Ok(trackers)
     }
 
+    async fn get_tracker_details(&self, target: &SingleTarget) -> Result<TrackerDetails, Error> {
+        Ok(tracker_details(&self._trackers_raw(target).await?))
+    }
+
     async fn remove_tracker(&self, target: &SingleTarget, tracker: &str) -> Result<(), Error> {
         //.context(InfoHashError as <ToSingleTarget::Error>)?;
         let truncated = target.truncated();
-        let res = self
-            ._post(self._endpoint_params(
-                "torrent/removeTrackers",
-                vec![("hash", truncated), ("urls", tracker)],
-            ))
-            .await?;
-
-        match res.status() {
-            StatusCode::NOT_FOUND => Err(Error::MissingTorrent {
-                hash: target.to_string(),
-            }),
-            StatusCode::CONFLICT => {
+        self._retry(false, || async {
+            let res = self
+                ._post(self._endpoint_params(
+                    "torrents/removeTrackers",
+                    vec![("hash", truncated), ("urls", tracker)],
+                ))
+                .await?;
+            if res.status() == StatusCode::CONFLICT {
                 // Tracker URL was not found
-                Ok(())
+                return Ok(());
             }
-            _ => Ok(()),
-        }
+            check(res, Some(target.to_string())).await?;
+            Ok(())
+        })
+        .await
     }
 
     async fn add_tracker(&self, target: &SingleTarget, tracker: &str) -> Result<(), Error> {
         let truncated = target.truncated();
-        let res = self
-            ._post(self._endpoint_params(
-                "torrent/addTrackers",
-                vec![("hash", truncated), ("urls", tracker)],
-            ))
-            .await?;
+        self._retry(false, || async {
+            let res = self
+                ._post(self._endpoint_params(
+                    "torrents/addTrackers",
+                    vec![("hash", truncated), ("urls", tracker)],
+                ))
+                .await?;
+            check(res, Some(target.to_string())).await
+        })
+        .await?;
+        Ok(())
+    }
 
-        if res.status().is_success() {
-            Ok(())
-        } else {
-            Err(Error::MissingTorrent {
-                hash: target.as_str().to_string(),
-            })
+    /// qBittorrent starts a new tier after every empty line in the list of trackers, so the
+    /// tracker is preceded by as many empty lines as its tier.
+    async fn add_tracker_tier(
+        &self,
+        target: &SingleTarget,
+        tracker: &str,
+        tier: u32,
+    ) -> Result<(), Error> {
+        let version = self.negotiated_version().await?;
+        if !version.capabilities().supports(Operation::AddTrackerTier) {
+            return Err(Error::UnsupportedOperation {
+                endpoint: "torrents/addTrackers".to_string(),
+                message: format!(
+                    "tracker tiers need qBittorrent {}",
+                    QBittorrentVersion::TRACKER_TIERS_SINCE
+                ),
+            });
         }
+
+        let urls = format!("{}{tracker}", "\n".repeat(tier as usize));
+        self.add_tracker(target, &urls).await
+    }
+
+    async fn edit_tracker(&self, target: &SingleTarget, from: &str, to: &str) -> Result<(), Error> {
+        let truncated = target.truncated();
+        self._retry(false, || async {
+            let res = self
+                ._post(self._endpoint_params(
+                    "torrents/editTracker",
+                    vec![("hash", truncated), ("origUrl", from), ("newUrl", to)],
+                ))
+                .await?;
+            check(res, Some(target.to_string())).await
+        })
+        .await?;
+        Ok(())
     }
 
     async fn get_files(&self, target: &SingleTarget) -> Result<Vec<TorrentContent>, Error> {
@@ -341,69 +949,76 @@ impl Api for QBittorrentClient {
             });
         };
 
-        let mut form = Form::new();
-        form = form.text("hash", id.as_str().to_string());
-        let res = self
-            ._post_multipart(self._endpoint("torrents/files"), form)
-            .await?;
+        let form = || Ok(Form::new().text("hash", id.as_str().to_string()));
+        self._retry(true, || async {
+            let res = self
+                ._post_multipart(self._endpoint("torrents/files"), form)
+                .await?;
 
-        if res.status().is_success() {
+            let res = check(res, Some(target.to_string())).await?;
             let concrete: Vec<QBittorrentTorrentContent> = self._json(res).await?;
             Ok(concrete.iter().map(|t| t.to_torrent_content()).collect())
-        } else {
-            Err(Error::MissingTorrent {
+        })
+        .await
+    }
+
+    async fn get_settings(&self, target: &SingleTarget) -> Result<TorrentSettings, Error> {
+        let torrents = self
+            ._list_target_raw(&MultiTarget::Hash(target.clone()))
+            .await?;
+        // _list_target_raw never returns an empty list for a single target
+        Ok(torrents
+            .first()
+            .map(|torrent| torrent.to_settings())
+            .unwrap_or_default())
+    }
+
+    async fn export(&self, target: &SingleTarget) -> Result<Vec<u8>, Error> {
+        let Some(id) = self.id(target).await? else {
+            return Err(Error::MissingTorrent {
                 hash: target.as_str().to_string(),
-            })
-        }
+            });
+        };
+
+        self._retry(true, || async {
+            let res = self
+                ._post(self._endpoint_params("torrents/export", vec![("hash", id.as_str())]))
+                .await?;
+
+            if res.status() == StatusCode::CONFLICT {
+                return Err(Error::MissingMetadata {
+                    hash: target.as_str().to_string(),
+                });
+            }
+            let res = check(res, Some(target.to_string())).await?;
+            let bytes = res.bytes().await.map_err(request_error)?;
+            Ok(bytes.to_vec())
+        })
+        .await
     }
 }
 
 #[async_trait]
 impl<'a> ApiAdd<'a> for QBittorrentClient {
     async fn api_add_send(&self, add: AddBuilder<'a, AddSource>) -> Result<(), ApiError> {
+        let version = self.negotiated_version().await?;
+        add.ensure_supported(&version.capabilities())?;
+        let fields = add_fields(&add, &version);
+
         match add.source {
             AddSource::MagnetStr(url) => {
-                let mut form = Form::new();
-
-                if let Some(save_path) = add.save_path {
-                    form = form.text("savepath", save_path);
-                }
-
-                if let Some(paused) = add.paused {
-                    form = form.text("stopped", paused.to_string());
-                }
-
-                if let Some(tags) = add.tags {
-                    form = form.text("tags", tags.join(","));
-                }
-                form = form.text("urls", url);
-                let res = self
-                    ._post_multipart(self._endpoint("torrents/add"), form)
-                    .await?;
-                add_success(res).await
+                let form = || async { Ok(add_form(&fields).text("urls", url.clone())) };
+                self._post_add(form).await
             }
             AddSource::MagnetFile(path) => {
-                let mut form = Form::new();
-
-                if let Some(save_path) = add.save_path {
-                    form = form.text("savepath", save_path);
-                }
-
-                if let Some(paused) = add.paused {
-                    form = form.text("stopped", paused.to_string());
-                }
-
-                if let Some(tags) = add.tags {
-                    form = form.text("tags", tags.join(","));
-                }
-                let content = std::fs::read_to_string(&path).context(FailedReadTorrentError {
-                    path: path.to_path_buf(),
-                })?;
-                form = form.text("urls", content);
-                let res = self
-                    ._post_multipart(self._endpoint("torrents/add"), form)
-                    .await?;
-                add_success(res).await
+                let content =
+                    tokio::fs::read_to_string(&path)
+                        .await
+                        .context(FailedReadTorrentError {
+                            path: path.to_path_buf(),
+                        })?;
+                let form = || async { Ok(add_form(&fields).text("urls", content.clone())) };
+                self._post_add(form).await
             }
             AddSource::TorrentFile(path) => {
                 // Form.file() is not supported in async reqwest::multipart::Form
@@ -412,56 +1027,493 @@ impl<'a> ApiAdd<'a> for QBittorrentClient {
                     .file_name()
                     .map(|val| val.to_string_lossy().to_string())
                     .unwrap_or_default();
-                let mut file = File::open(&path).await.context(FailedReadTorrentError {
-                    path: path.to_path_buf(),
-                })?;
-                let mut file_bytes: Vec<u8> = Vec::new();
-                file.read_to_end(&mut file_bytes)
+                let length = tokio::fs::metadata(&path)
                     .await
                     .context(FailedReadTorrentError {
                         path: path.to_path_buf(),
+                    })?
+                    .len();
+
+                // The file is streamed instead of buffered in memory, but QBittorrent does not
+                // support chunked requests, so the exact length of the part must be known.
+                // Then the whole form has a known length, and Content-Length is set by reqwest.
+                // The file is opened again for every attempt, so a replayed request starts
+                // from the beginning of the file.
+                let form = || async {
+                    let file = File::open(&path).await.context(FailedReadTorrentError {
+                        path: path.to_path_buf(),
                     })?;
-                //let reader = Body::wrap_stream(FramedRead::new(file, BytesCodec::new()));
+                    let reader = Body::wrap_stream(ReaderStream::new(file));
+                    Ok(add_form(&fields).part(
+                        "torrents",
+                        Part::stream_with_length(reader, length).file_name(file_name.clone()),
+                    ))
+                };
+
+                self._post_add(form).await
+            }
+            AddSource::TorrentBytes(bytes) => {
+                self._add_torrent_bytes(&fields, bytes, "upload.torrent".to_string())
+                    .await
+            }
+            AddSource::TorrentParsed(torrent) => {
+                let file_name = format!("{}.torrent", torrent.name());
+                self._add_torrent_bytes(&fields, torrent.to_vec(), file_name)
+                    .await
+            }
+            AddSource::Url(url) => {
+                let form = || async { Ok(add_form(&fields).text("urls", url.clone())) };
+                self._post_add(form).await
+            }
+        }
+    }
+
+    /// Adds many torrents, packing the ones with the same options into few requests.
+    ///
+    /// Duplicates are detected before sending, and the list of torrents is checked
+    /// afterwards to tell which torrents were actually added. Torrents which can't be sent,
+    /// or whose request failed, are reported as `BatchAddResult::Failed`.
+    async fn api_add_batch(
+        &self,
+        items: Vec<AddBuilder<'a, AddSource>>,
+    ) -> Result<Vec<BatchAddResult>, ApiError> {
+        let mut known: HashSet<TorrentID> = self
+            ._list_target_raw(&MultiTarget::All)
+            .await?
+            .into_iter()
+            .map(|torrent| torrent.id)
+            .collect();
+        let mut results: Vec<Option<BatchAddResult>> = items.iter().map(|_| None).collect();
+        let mut batches: Vec<AddBatch> = Vec::new();
 
-                let mut form = Form::new()
-                    //.part("torrents", Part::stream(reader).file_name(file_name));
-                    .part("torrents", Part::bytes(file_bytes).file_name(file_name));
+        let version = self.negotiated_version().await?;
+        let capabilities = version.capabilities();
 
-                if let Some(paused) = add.paused {
-                    form = form.text("stopped", paused.to_string());
+        for (index, item) in items.into_iter().enumerate() {
+            if let Err(e) = item.ensure_supported(&capabilities) {
+                results[index] = Some(BatchAddResult::Failed(Arc::new(e)));
+                continue;
+            }
+            let fields = add_fields(&item, &version);
+
+            let payload = match AddPayload::load(item.source).await {
+                Ok(payload) => payload,
+                Err(e) => {
+                    results[index] = Some(BatchAddResult::Failed(Arc::new(e)));
+                    continue;
                 }
+            };
+
+            if let Some(hash) = &payload.hash
+                && !known.insert(hash.id())
+            {
+                results[index] = Some(BatchAddResult::Duplicate(hash.clone()));
+                continue;
+            }
 
-                if let Some(tags) = add.tags {
-                    form = form.text("tags", tags.join(","));
+            match batches.iter_mut().find(|batch| {
+                batch.fields == fields && batch.size + payload.size() <= ADD_BATCH_MAX_SIZE
+            }) {
+                Some(batch) => batch.push(index, payload),
+                None => {
+                    let mut batch = AddBatch {
+                        fields,
+                        size: 0,
+                        items: Vec::new(),
+                    };
+                    batch.push(index, payload);
+                    batches.push(batch);
                 }
+            }
+        }
 
-                if let Some(save_path) = add.save_path {
-                    form = form.text("savepath", save_path);
+        let mut sent: Vec<(usize, InfoHash)> = Vec::new();
+        for batch in batches {
+            let indexes: Vec<usize> = batch.items.iter().map(|(index, _)| *index).collect();
+            match self._add_batch(batch).await {
+                Ok((accepted, items)) => {
+                    for (index, hash) in items {
+                        match hash {
+                            Some(hash) => sent.push((index, hash)),
+                            // Torrents from URLs can't be checked, trust the API response
+                            None if accepted => results[index] = Some(BatchAddResult::Added(None)),
+                            None => results[index] = Some(BatchAddResult::Rejected(None)),
+                        }
+                    }
+                }
+                // Earlier batches were already added, so keep going
+                Err(e) => {
+                    let e = Arc::new(e);
+                    for index in indexes {
+                        results[index] = Some(BatchAddResult::Failed(e.clone()));
+                    }
                 }
+            }
+        }
 
-                let res = self
-                    ._post_multipart(self._endpoint("torrents/add"), form)
-                    .await?;
-                add_success(res).await
+        // QBittorrent adds torrents asynchronously, so accepted torrents may take a moment
+        // to appear in the list
+        let timeout = self.batch_check_timeout.unwrap_or_else(|| {
+            ADD_BATCH_CHECK_TIMEOUT + ADD_BATCH_CHECK_PER_TORRENT * sent.len() as u32
+        });
+        let deadline = Instant::now() + timeout;
+        while !sent.is_empty() {
+            let added: HashSet<TorrentID> = match self._list_target_raw(&MultiTarget::All).await {
+                Ok(torrents) => torrents.into_iter().map(|torrent| torrent.id).collect(),
+                Err(e) => {
+                    let e = Arc::new(e);
+                    for (index, _) in sent.drain(..) {
+                        results[index] = Some(BatchAddResult::Failed(e.clone()));
+                    }
+                    break;
+                }
+            };
+            sent.retain(|(index, hash)| {
+                let is_added = added.contains(&hash.id());
+                if is_added {
+                    results[*index] = Some(BatchAddResult::Added(Some(hash.clone())));
+                }
+                !is_added
+            });
+
+            let now = Instant::now();
+            if now >= deadline {
+                for (index, hash) in sent.drain(..) {
+                    results[index] = Some(BatchAddResult::NotYetVisible(hash));
+                }
+            } else if !sent.is_empty() {
+                sleep(ADD_WAIT_INTERVAL.min(deadline - now)).await;
             }
         }
+
+        // Every item was given a result above
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    async fn api_add_state(&self, hash: &InfoHash) -> Result<Option<AddedState>, ApiError> {
+        let id = hash.id();
+        let torrents = self._list_target_raw(&MultiTarget::All).await?;
+
+        Ok(torrents
+            .iter()
+            .find(|torrent| torrent.id == id)
+            .map(|torrent| {
+                if torrent.has_metadata() {
+                    AddedState::Ready
+                } else {
+                    AddedState::MissingMetadata
+                }
+            }))
     }
 }
 
+/// How long to wait for the torrents of a batch to appear in the list, before reporting
+/// the missing ones as not yet visible
+const ADD_BATCH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Extra time to wait for every torrent of a batch, as loading them takes longer
+const ADD_BATCH_CHECK_PER_TORRENT: Duration = Duration::from_millis(50);
+
+/// Maximum size of a batch add request.
+///
+/// QBittorrent refuses requests larger than 64MiB, so we leave some room for the other fields.
+const ADD_BATCH_MAX_SIZE: usize = 60 * 1024 * 1024;
+
+/// Torrents sharing the same options, sent in a single `torrents/add` request
+struct AddBatch {
+    fields: Vec<(&'static str, String)>,
+    size: usize,
+    items: Vec<(usize, AddPayload)>,
+}
+
+impl AddBatch {
+    fn push(&mut self, index: usize, payload: AddPayload) {
+        self.size += payload.size();
+        self.items.push((index, payload));
+    }
+}
+
+/// A torrent source loaded in memory, ready to be sent in a batch
+struct AddPayload {
+    hash: Option<InfoHash>,
+    content: AddContent,
+}
+
+enum AddContent {
+    Url(String),
+    Torrent(Vec<u8>),
+}
+
+impl AddPayload {
+    async fn load(source: AddSource) -> Result<AddPayload, Error> {
+        match source {
+            AddSource::MagnetStr(magnet) => Ok(AddPayload {
+                hash: Some(magnet_infohash(&magnet)?),
+                content: AddContent::Url(magnet),
+            }),
+            AddSource::MagnetFile(path) => {
+                let magnet =
+                    tokio::fs::read_to_string(&path)
+                        .await
+                        .context(FailedReadTorrentError {
+                            path: path.to_path_buf(),
+                        })?;
+                let magnet = magnet.trim().to_string();
+                Ok(AddPayload {
+                    hash: Some(magnet_infohash(&magnet)?),
+                    content: AddContent::Url(magnet),
+                })
+            }
+            AddSource::TorrentFile(path) => {
+                let bytes = tokio::fs::read(&path)
+                    .await
+                    .context(FailedReadTorrentError {
+                        path: path.to_path_buf(),
+                    })?;
+                Ok(AddPayload {
+                    hash: Some(torrent_infohash(&bytes)?),
+                    content: AddContent::Torrent(bytes),
+                })
+            }
+            AddSource::TorrentBytes(bytes) => Ok(AddPayload {
+                hash: Some(torrent_infohash(&bytes)?),
+                content: AddContent::Torrent(bytes),
+            }),
+            AddSource::TorrentParsed(torrent) => Ok(AddPayload {
+                hash: Some(torrent.hash.clone()),
+                content: AddContent::Torrent(torrent.to_vec()),
+            }),
+            AddSource::Url(url) => Ok(AddPayload {
+                hash: None,
+                content: AddContent::Url(url),
+            }),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match &self.content {
+            AddContent::Url(url) => url.len(),
+            AddContent::Torrent(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl QBittorrentClient {
+    /// Sends a batch of torrents in a single request.
+    ///
+    /// Returns whether QBittorrent accepted the request, along with the
+    /// index and infohash of every torrent in the batch.
+    async fn _add_batch(
+        &self,
+        batch: AddBatch,
+    ) -> Result<(bool, Vec<(usize, Option<InfoHash>)>), Error> {
+        let mut urls: Vec<String> = Vec::new();
+        let mut torrents: Vec<(usize, Bytes)> = Vec::new();
+        let mut items = Vec::with_capacity(batch.items.len());
+        for (index, payload) in batch.items {
+            match payload.content {
+                AddContent::Url(url) => urls.push(url),
+                AddContent::Torrent(bytes) => torrents.push((index, Bytes::from(bytes))),
+            }
+            items.push((index, payload.hash));
+        }
+
+        let form = || async {
+            let mut form = add_form(&batch.fields);
+            for (index, bytes) in &torrents {
+                form = form.part(
+                    "torrents",
+                    bytes_part(bytes.clone()).file_name(format!("{index}.torrent")),
+                );
+            }
+            if !urls.is_empty() {
+                form = form.text("urls", urls.join("\n"));
+            }
+            Ok(form)
+        };
+
+        match self._post_add(form).await {
+            Ok(()) => Ok((true, items)),
+            Err(Error::RejectedTorrent) => Ok((false, items)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Uploads a .torrent file from memory.
+    ///
+    /// The whole file is sent as a single part of known length, because
+    /// QBittorrent does not support chunked requests.
+    async fn _add_torrent_bytes(
+        &self,
+        fields: &[(&'static str, String)],
+        bytes: Vec<u8>,
+        file_name: String,
+    ) -> Result<(), Error> {
+        let bytes = Bytes::from(bytes);
+        let form = || async {
+            Ok(add_form(fields).part(
+                "torrents",
+                bytes_part(bytes.clone()).file_name(file_name.clone()),
+            ))
+        };
+
+        self._post_add(form).await
+    }
+
+    /// Sends a `torrents/add` request, retried only if the retry policy allows
+    /// non-idempotent operations.
+    ///
+    /// The form is built by an async closure, so that files can be opened again without
+    /// blocking when the request is replayed.
+    async fn _post_add<F, Fut>(&self, form: F) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<Form, Error>> + Send,
+    {
+        let endpoint = self._endpoint("torrents/add");
+        self._retry(false, || async {
+            let res = self
+                ._send(|| async { Ok(self.client.post(endpoint.clone()).multipart(form().await?)) })
+                .await?;
+            add_success(res).await
+        })
+        .await
+    }
+}
+
+/// Returns a form part for in-memory bytes.
+///
+/// The part has a known length, so the request is not chunked, and cloning the bytes
+/// to replay a request does not copy them.
+fn bytes_part(bytes: Bytes) -> Part {
+    let length = bytes.len() as u64;
+    Part::stream_with_length(Body::from(bytes), length)
+}
+
+/// Builds the `torrents/add` form with the requested options, without the torrent source
+fn add_form(fields: &[(&'static str, String)]) -> Form {
+    fields.iter().fold(Form::new(), |form, (name, value)| {
+        form.text(*name, value.to_string())
+    })
+}
+
+/// Returns the `torrents/add` form fields for the requested options
+fn add_fields<S>(
+    add: &AddBuilder<'_, S>,
+    version: &QBittorrentVersion,
+) -> Vec<(&'static str, String)> {
+    let mut fields = Vec::new();
+
+    if let Some(save_path) = &add.save_path {
+        fields.push(("savepath", save_path.to_string()));
+    }
+
+    if let Some(paused) = add.paused {
+        fields.push((version.stopped_field(), paused.to_string()));
+    }
+
+    if let Some(tags) = &add.tags {
+        fields.push(("tags", tags.join(",")));
+    }
+
+    if let Some(category) = &add.category {
+        fields.push(("category", category.to_string()));
+    }
+
+    if let Some(rename) = &add.rename {
+        fields.push(("rename", rename.to_string()));
+    }
+
+    if let Some(skip_checking) = add.skip_checking {
+        fields.push(("skip_checking", skip_checking.to_string()));
+    }
+
+    if let Some(content_layout) = add.content_layout {
+        let content_layout = match content_layout {
+            ContentLayout::Original => "Original",
+            ContentLayout::Subfolder => "Subfolder",
+            ContentLayout::NoSubfolder => "NoSubfolder",
+        };
+        fields.push(("contentLayout", content_layout.to_string()));
+    }
+
+    if let Some(stop_condition) = add.stop_condition {
+        let stop_condition = match stop_condition {
+            StopCondition::None => "None",
+            StopCondition::MetadataReceived => "MetadataReceived",
+            StopCondition::FilesChecked => "FilesChecked",
+        };
+        fields.push(("stopCondition", stop_condition.to_string()));
+    }
+
+    if let Some(auto_management) = add.auto_management {
+        fields.push(("autoTMM", auto_management.to_string()));
+    }
+
+    if let Some(download_path) = &add.download_path {
+        fields.push(("useDownloadPath", "true".to_string()));
+        fields.push(("downloadPath", download_path.to_string()));
+    }
+
+    if let Some(upload_limit) = add.upload_limit {
+        fields.push(("upLimit", upload_limit.to_string()));
+    }
+
+    if let Some(download_limit) = add.download_limit {
+        fields.push(("dlLimit", download_limit.to_string()));
+    }
+
+    if let Some(ratio_limit) = add.ratio_limit {
+        fields.push(("ratioLimit", limit_field(ratio_limit, |r| r.to_string())));
+    }
+
+    if let Some(seeding_time_limit) = add.seeding_time_limit {
+        // QBittorrent expects minutes, and 0 means no seeding at all, so round up
+        let minutes = |limit: Duration| limit.as_nanos().div_ceil(60_000_000_000).to_string();
+        fields.push(("seedingTimeLimit", limit_field(seeding_time_limit, minutes)));
+    }
+
+    if let Some(sequential_download) = add.sequential_download {
+        fields.push(("sequentialDownload", sequential_download.to_string()));
+    }
+
+    if let Some(first_last_piece_priority) = add.first_last_piece_priority {
+        fields.push(("firstLastPiecePrio", first_last_piece_priority.to_string()));
+    }
+
+    if let Some(add_to_top_of_queue) = add.add_to_top_of_queue {
+        fields.push(("addToTopOfQueue", add_to_top_of_queue.to_string()));
+    }
+
+    fields
+}
+
+/// Returns the form value of a seeding limit, which is -2 for the global limit and -1 when
+/// unlimited
+fn limit_field<T>(limit: SeedingLimit<T>, value: impl Fn(T) -> String) -> String {
+    match limit {
+        SeedingLimit::Global => "-2".to_string(),
+        SeedingLimit::Unlimited => "-1".to_string(),
+        SeedingLimit::Limit(limit) => value(limit),
+    }
+}
+
+/// Checks the response of the `torrents/add` endpoint.
+///
+/// QBittorrent does not tell a duplicate from an invalid torrent, so both are
+/// reported as `RejectedTorrent`, and `AddBuilder::send` tells them apart.
 async fn add_success(res: reqwest::Response) -> Result<(), Error> {
     if res.status().is_success() {
-        if res
-            .text()
-            .await
-            .boxed()
-            .context(HttpError)?
-            .starts_with("Fail")
-        {
+        if res.text().await.map_err(request_error)?.starts_with("Fail") {
             Err(Error::RejectedTorrent)
         } else {
             Ok(())
         }
-    } else {
+    } else if res.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+        // The torrent file is not valid
         Err(Error::RejectedTorrent)
+    } else {
+        check(res, None).await?;
+        Ok(())
     }
 }
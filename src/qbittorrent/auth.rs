@@ -0,0 +1,52 @@
+use reqwest::RequestBuilder;
+
+/// How the client authenticates to qBittorrent itself
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Auth {
+    /// Log in with the username and password, and send the session cookie (SID)
+    #[default]
+    Login,
+    /// Don't log in, for daemons with authentication bypassed for the client (such as
+    /// "Bypass authentication for clients on localhost" or an IP subnet whitelist)
+    Bypass,
+    /// Send an API key with every request, instead of logging in (qBittorrent v5.2 and later)
+    ApiKey(String),
+}
+
+impl Auth {
+    /// Whether a session cookie is obtained by logging in
+    pub fn uses_login(&self) -> bool {
+        matches!(self, Auth::Login)
+    }
+}
+
+/// Credentials for a reverse proxy in front of qBittorrent (such as nginx or an SSO proxy),
+/// sent with every request alongside the qBittorrent session cookie
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HttpAuth {
+    /// HTTP Basic authentication
+    Basic {
+        user: String,
+        password: Option<String>,
+    },
+    /// `Authorization: Bearer` token
+    Bearer(String),
+}
+
+/// Adds the reverse proxy credentials or the qBittorrent API key to a request.
+///
+/// Both use the `Authorization` header, so the builder refuses to set both.
+pub(crate) fn authorize(
+    request: RequestBuilder,
+    auth: &Auth,
+    http_auth: Option<&HttpAuth>,
+) -> RequestBuilder {
+    match (auth, http_auth) {
+        (Auth::ApiKey(key), _) => request.bearer_auth(key),
+        (_, Some(HttpAuth::Basic { user, password })) => {
+            request.basic_auth(user, password.as_ref())
+        }
+        (_, Some(HttpAuth::Bearer(token))) => request.bearer_auth(token),
+        (_, None) => request,
+    }
+}
@@ -0,0 +1,289 @@
+use reqwest::cookie::Jar;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, ClientBuilder, Proxy};
+use snafu::ResultExt;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ApiError;
+use crate::api_error::*;
+use crate::qbittorrent::tls::{TlsOptions, parse_fingerprint};
+use crate::qbittorrent::{Auth, HttpAuth, QBittorrentClient, SessionStore};
+use crate::retry::RetryPolicy;
+
+/// Configures the HTTP client of a [QBittorrentClient], as returned by
+/// [QBittorrentClient::builder].
+///
+/// By default, requests never time out. Set a timeout so a hung daemon can't block callers
+/// forever:
+///
+/// ```no_run
+/// use hightorrent_api::QBittorrentClient;
+/// use std::time::Duration;
+///
+/// # async fn run() -> Result<(), hightorrent_api::ApiError> {
+/// let client = QBittorrentClient::builder("http://localhost:8080", "admin", "adminadmin")
+///     .connect_timeout(Duration::from_secs(5))
+///     .timeout(Duration::from_secs(30))
+///     .proxy("socks5://localhost:1080")
+///     .login()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct QBittorrentClientBuilder {
+    host: String,
+    user: String,
+    password: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    default_headers: HeaderMap,
+    proxy: Option<String>,
+    tls: TlsOptions,
+    fingerprint: Option<String>,
+    client: Option<Client>,
+    session_store: Option<Arc<dyn SessionStore>>,
+    retry: Option<RetryPolicy>,
+    batch_check_timeout: Option<Duration>,
+    auth: Auth,
+    http_auth: Option<HttpAuth>,
+}
+
+impl QBittorrentClientBuilder {
+    pub fn new(host: &str, user: &str, password: &str) -> QBittorrentClientBuilder {
+        QBittorrentClientBuilder {
+            host: host.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+            timeout: None,
+            connect_timeout: None,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            user_agent: None,
+            default_headers: HeaderMap::new(),
+            proxy: None,
+            tls: TlsOptions::default(),
+            fingerprint: None,
+            client: None,
+            session_store: None,
+            retry: None,
+            batch_check_timeout: None,
+            auth: Auth::default(),
+            http_auth: None,
+        }
+    }
+
+    /// Timeout for a whole request, from connecting until the response body is read
+    pub fn timeout(mut self, timeout: Duration) -> QBittorrentClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for connecting to the daemon
+    pub fn connect_timeout(mut self, timeout: Duration) -> QBittorrentClientBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum number of idle connections kept open to the daemon
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> QBittorrentClientBuilder {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle connections are kept open
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> QBittorrentClientBuilder {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> QBittorrentClientBuilder {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Headers sent with every request, such as those required by a reverse proxy
+    pub fn default_headers(mut self, headers: HeaderMap) -> QBittorrentClientBuilder {
+        self.default_headers = headers;
+        self
+    }
+
+    /// Sends all requests through a proxy: `http://`, `https://` or `socks5://` (`socks5h://`
+    /// to resolve host names through the proxy). Credentials may be part of the URL.
+    pub fn proxy(mut self, proxy: &str) -> QBittorrentClientBuilder {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Trusts an additional root certificate (PEM), such as a private certificate authority.
+    ///
+    /// HTTPS requires the `rustls` or `native-tls` feature.
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> QBittorrentClientBuilder {
+        self.tls.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// Only accepts the server certificate with this SHA-256 fingerprint, in hexadecimal with
+    /// optional colons, as printed by `openssl x509 -noout -fingerprint -sha256`.
+    ///
+    /// This accepts a self-signed certificate without disabling verification altogether.
+    /// Requires the `rustls` feature.
+    pub fn certificate_fingerprint(mut self, sha256: &str) -> QBittorrentClientBuilder {
+        self.fingerprint = Some(sha256.to_string());
+        self
+    }
+
+    /// Authenticates with a client certificate (PEM chain) and its PKCS#8 private key (PEM),
+    /// for reverse proxies requiring mutual TLS
+    pub fn client_certificate(mut self, cert: &[u8], key: &[u8]) -> QBittorrentClientBuilder {
+        self.tls.identity = Some((cert.to_vec(), key.to_vec()));
+        self
+    }
+
+    /// Accepts any certificate, including self-signed and expired ones.
+    ///
+    /// Prefer [certificate_fingerprint](Self::certificate_fingerprint), which still
+    /// authenticates the server.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> QBittorrentClientBuilder {
+        self.tls.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Uses a pre-built HTTP client, instead of building one from the settings above.
+    ///
+    /// The timeouts, pool, user agent, headers, proxy and TLS settings of this builder are then
+    /// ignored.
+    pub fn client(mut self, client: Client) -> QBittorrentClientBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    /// See [QBittorrentClient::session_store]
+    pub fn session_store<S: SessionStore + 'static>(
+        mut self,
+        store: S,
+    ) -> QBittorrentClientBuilder {
+        self.session_store = Some(Arc::new(store));
+        self
+    }
+
+    /// See [QBittorrentClient::retry_policy]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> QBittorrentClientBuilder {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// See [QBittorrentClient::batch_check_timeout]
+    pub fn batch_check_timeout(mut self, timeout: Duration) -> QBittorrentClientBuilder {
+        self.batch_check_timeout = Some(timeout);
+        self
+    }
+
+    /// How to authenticate to qBittorrent: log in (the default), bypass authentication, or an
+    /// API key
+    pub fn auth(mut self, auth: Auth) -> QBittorrentClientBuilder {
+        self.auth = auth;
+        self
+    }
+
+    /// Sends HTTP Basic credentials with every request, for a reverse proxy in front of
+    /// qBittorrent. They can't be combined with an API key.
+    pub fn basic_auth(mut self, user: &str, password: Option<&str>) -> QBittorrentClientBuilder {
+        self.http_auth = Some(HttpAuth::Basic {
+            user: user.to_string(),
+            password: password.map(str::to_string),
+        });
+        self
+    }
+
+    /// Sends a bearer token with every request, for a reverse proxy in front of qBittorrent.
+    /// It can't be combined with an API key.
+    pub fn bearer_auth(mut self, token: &str) -> QBittorrentClientBuilder {
+        self.http_auth = Some(HttpAuth::Bearer(token.to_string()));
+        self
+    }
+
+    /// Builds the HTTP client from the settings, storing its cookies in the jar
+    fn http_client(&self, jar: Arc<Jar>) -> Result<Client, ApiError> {
+        let mut builder = ClientBuilder::new()
+            .cookie_provider(jar)
+            .default_headers(self.default_headers.clone());
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy).boxed().context(ClientInitError)?);
+        }
+
+        let mut tls = self.tls.clone();
+        if let Some(fingerprint) = &self.fingerprint {
+            tls.fingerprint = Some(parse_fingerprint(fingerprint)?);
+        }
+        builder = tls.apply(builder)?;
+
+        builder.build().boxed().context(ClientInitError)
+    }
+
+    /// Builds a client that's not logged in yet.
+    ///
+    /// Then perform `QBittorrentClient::do_login` to actually login.
+    pub fn build(self) -> Result<QBittorrentClient, ApiError> {
+        if matches!(self.auth, Auth::ApiKey(_)) && self.http_auth.is_some() {
+            return Err(ApiError::ClientInit {
+                source:
+                    "An API key and reverse proxy credentials both need the Authorization header"
+                        .into(),
+            });
+        }
+
+        let (http_client, jar) = match &self.client {
+            Some(client) => (client.clone(), None),
+            None => {
+                let jar = Arc::new(Jar::default());
+                (self.http_client(jar.clone())?, Some(jar))
+            }
+        };
+
+        let mut client =
+            QBittorrentClient::from_client(&self.host, &self.user, &self.password, http_client)?
+                .authentication(self.auth, self.http_auth);
+        if let Some(jar) = jar {
+            client = client.cookie_jar(jar);
+        }
+        if let Some(store) = self.session_store {
+            client = client.session_store(store);
+        }
+        if let Some(policy) = self.retry {
+            client = client.retry_policy(policy);
+        }
+        if let Some(timeout) = self.batch_check_timeout {
+            client = client.batch_check_timeout(timeout);
+        }
+        Ok(client)
+    }
+
+    /// Builds the client and logs in
+    pub async fn login(self) -> Result<QBittorrentClient, ApiError> {
+        let client = self.build()?;
+        client.do_login().await?;
+        Ok(client)
+    }
+}
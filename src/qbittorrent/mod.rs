@@ -1,5 +1,19 @@
 mod api;
 pub use api::QBittorrentClient;
 
+mod auth;
+pub use auth::{Auth, HttpAuth};
+
+mod builder;
+pub use builder::QBittorrentClientBuilder;
+
+mod tls;
+
 mod torrent;
 pub use torrent::{QBittorrentTorrent, QBittorrentTorrentContent, QBittorrentTracker};
+
+mod version;
+pub use version::{QBittorrentVersion, Version, normalize_state};
+
+mod session;
+pub use session::{FileSessionStore, SessionStore};
@@ -0,0 +1,74 @@
+use snafu::ResultExt;
+use tokio::io::AsyncWriteExt;
+
+use std::path::{Path, PathBuf};
+
+use crate::ApiError;
+use crate::api_error::*;
+
+/// Persists the QBittorrent session cookie (SID), so a new process can reuse it instead of
+/// logging in again.
+///
+/// QBittorrent rate-limits logins and bans IP addresses after failed logins, so short-lived
+/// processes should reuse their session.
+#[async_trait]
+pub trait SessionStore: std::fmt::Debug + Send + Sync {
+    /// Returns the stored session cookie, or None when there is none
+    async fn load(&self) -> Result<Option<String>, ApiError>;
+    /// Stores the session cookie after logging in
+    async fn save(&self, sid: &str) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+impl<S: SessionStore + ?Sized> SessionStore for std::sync::Arc<S> {
+    async fn load(&self) -> Result<Option<String>, ApiError> {
+        (**self).load().await
+    }
+
+    async fn save(&self, sid: &str) -> Result<(), ApiError> {
+        (**self).save(sid).await
+    }
+}
+
+/// Stores the session cookie in a file, only readable by the current user on Unix
+#[derive(Clone, Debug)]
+pub struct FileSessionStore {
+    path: PathBuf,
+}
+
+impl FileSessionStore {
+    pub fn new(path: &Path) -> FileSessionStore {
+        FileSessionStore {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn load(&self) -> Result<Option<String>, ApiError> {
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(sid) => Ok(Some(sid.trim().to_string()).filter(|sid| !sid.is_empty())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(SessionStoreError {
+                path: self.path.to_path_buf(),
+            }),
+        }
+    }
+
+    async fn save(&self, sid: &str) -> Result<(), ApiError> {
+        let mut options = tokio::fs::OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&self.path).await.context(SessionStoreError {
+            path: self.path.to_path_buf(),
+        })?;
+        file.write_all(sid.as_bytes())
+            .await
+            .context(SessionStoreError {
+                path: self.path.to_path_buf(),
+            })
+    }
+}
@@ -0,0 +1,238 @@
+use reqwest::ClientBuilder;
+
+use crate::ApiError;
+
+/// TLS settings for HTTPS connections, applied by the [QBittorrentClientBuilder](super::QBittorrentClientBuilder)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TlsOptions {
+    /// Additional root certificates (PEM)
+    pub root_certificates: Vec<Vec<u8>>,
+    /// SHA-256 fingerprint of the only accepted server certificate
+    pub fingerprint: Option<[u8; 32]>,
+    /// Client certificate chain and private key (PEM), for mutual TLS
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsOptions {
+    /// Configures the HTTP client for these settings.
+    ///
+    /// When both the `rustls` and `native-tls` features are enabled, rustls is used.
+    pub fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder, ApiError> {
+        if *self == TlsOptions::default() {
+            return Ok(builder);
+        }
+
+        #[cfg(feature = "rustls")]
+        {
+            rustls_backend::apply(self, builder)
+        }
+        #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+        {
+            native_tls_backend::apply(self, builder)
+        }
+        #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+        {
+            let _ = builder;
+            Err(client_init_error(
+                "TLS settings require the rustls or native-tls feature",
+            ))
+        }
+    }
+}
+
+/// Parses a SHA-256 fingerprint in hexadecimal, with optional colons (`AB:CD:...`)
+pub(crate) fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32], ApiError> {
+    let invalid = || client_init_error(&format!("Invalid SHA-256 fingerprint: {fingerprint}"));
+
+    let hex: Vec<u8> = fingerprint
+        .bytes()
+        .filter(|c| *c != b':' && !c.is_ascii_whitespace())
+        .collect();
+    if hex.len() != 64 {
+        return Err(invalid());
+    }
+
+    let mut bytes = [0; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+fn client_init_error(message: &str) -> ApiError {
+    ApiError::ClientInit {
+        source: message.into(),
+    }
+}
+
+#[cfg(feature = "native-tls")]
+#[cfg_attr(feature = "rustls", allow(dead_code))]
+mod native_tls_backend {
+    use reqwest::{Certificate, ClientBuilder, Identity};
+    use snafu::ResultExt;
+
+    use super::{TlsOptions, client_init_error};
+    use crate::ApiError;
+    use crate::api_error::*;
+
+    pub fn apply(
+        options: &TlsOptions,
+        mut builder: ClientBuilder,
+    ) -> Result<ClientBuilder, ApiError> {
+        builder = builder.use_native_tls();
+
+        if options.fingerprint.is_some() {
+            return Err(client_init_error(
+                "Certificate fingerprints require the rustls feature",
+            ));
+        }
+        for pem in &options.root_certificates {
+            builder = builder.add_root_certificate(
+                Certificate::from_pem(pem)
+                    .boxed()
+                    .context(ClientInitError)?,
+            );
+        }
+        if let Some((cert, key)) = &options.identity {
+            builder = builder.identity(
+                Identity::from_pkcs8_pem(cert, key)
+                    .boxed()
+                    .context(ClientInitError)?,
+            );
+        }
+
+        Ok(builder.danger_accept_invalid_certs(options.accept_invalid_certs))
+    }
+}
+
+#[cfg(feature = "rustls")]
+mod rustls_backend {
+    use reqwest::{Certificate, ClientBuilder, Identity};
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::WebPkiSupportedAlgorithms;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+    use rustls::{ClientConfig, DigitallySignedStruct, Error as TlsError, SignatureScheme};
+    use snafu::ResultExt;
+
+    use std::sync::Arc;
+
+    use super::TlsOptions;
+    use crate::ApiError;
+    use crate::api_error::*;
+
+    pub fn apply(
+        options: &TlsOptions,
+        mut builder: ClientBuilder,
+    ) -> Result<ClientBuilder, ApiError> {
+        if let Some(fingerprint) = options.fingerprint {
+            return pinned(options, fingerprint, builder);
+        }
+
+        builder = builder.use_rustls_tls();
+        for pem in &options.root_certificates {
+            builder = builder.add_root_certificate(
+                Certificate::from_pem(pem)
+                    .boxed()
+                    .context(ClientInitError)?,
+            );
+        }
+        if let Some((cert, key)) = &options.identity {
+            let pem = [cert.as_slice(), b"\n", key.as_slice()].concat();
+            builder = builder.identity(Identity::from_pem(&pem).boxed().context(ClientInitError)?);
+        }
+
+        Ok(builder.danger_accept_invalid_certs(options.accept_invalid_certs))
+    }
+
+    /// Only accepts the server certificate with the given fingerprint, whoever signed it.
+    ///
+    /// Root certificates are not needed then, and invalid certificates are never accepted.
+    fn pinned(
+        options: &TlsOptions,
+        fingerprint: [u8; 32],
+        builder: ClientBuilder,
+    ) -> Result<ClientBuilder, ApiError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = FingerprintVerifier {
+            fingerprint,
+            algorithms: provider.signature_verification_algorithms,
+        };
+
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .boxed()
+            .context(ClientInitError)?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+
+        let config = match &options.identity {
+            Some((cert, key)) => {
+                let chain = CertificateDer::pem_slice_iter(cert)
+                    .collect::<Result<Vec<_>, _>>()
+                    .boxed()
+                    .context(ClientInitError)?;
+                let key = PrivateKeyDer::from_pem_slice(key)
+                    .boxed()
+                    .context(ClientInitError)?;
+                config
+                    .with_client_auth_cert(chain, key)
+                    .boxed()
+                    .context(ClientInitError)?
+            }
+            None => config.with_no_client_auth(),
+        };
+
+        Ok(builder.use_preconfigured_tls(config))
+    }
+
+    #[derive(Debug)]
+    struct FingerprintVerifier {
+        fingerprint: [u8; 32],
+        algorithms: WebPkiSupportedAlgorithms,
+    }
+
+    impl ServerCertVerifier for FingerprintVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, TlsError> {
+            let digest = ring::digest::digest(&ring::digest::SHA256, end_entity);
+            if digest.as_ref() == self.fingerprint {
+                Ok(ServerCertVerified::assertion())
+            } else {
+                Err(TlsError::General(
+                    "Server certificate does not match the pinned fingerprint".to_string(),
+                ))
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, TlsError> {
+            rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, TlsError> {
+            rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.algorithms.supported_schemes()
+        }
+    }
+}
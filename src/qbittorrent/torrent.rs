@@ -5,6 +5,10 @@ use hightorrent::{
 use serde::{Deserialize, Deserializer, Serialize};
 
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::api::{TorrentSettings, TrackerDetails, TrackerEntry, TrackerStatus};
+use crate::qbittorrent::normalize_state;
 
 /// Deserializes from the 'info' endpoint of QBittorrent API
 /// [See QBittorrent API docs](https://github.com/qbittorrent/qBittorrent/wiki/WebUI-API-(qBittorrent-4.1)#get-torrent-list)
@@ -20,13 +24,43 @@ pub struct QBittorrentTorrent {
     pub progress: f32,
     #[serde(rename = "total_size")]
     pub size: i64,
+    /// State of the torrent, with qBittorrent v4 states renamed to their v5 equivalent
+    #[serde(deserialize_with = "load_state")]
     pub state: String,
     #[serde(deserialize_with = "load_tags")]
     pub tags: Vec<String>,
-    #[serde(rename = "hash")]
+    #[serde(rename = "hash", deserialize_with = "load_id")]
     pub id: TorrentID,
+    /// Bittorrent v1 infohash, empty for v2-only torrents and before qBittorrent v4.4
+    #[serde(default, deserialize_with = "load_infohash_v1")]
     pub infohash_v1: String,
+    /// Bittorrent v2 infohash, empty for v1-only torrents and before qBittorrent v4.4
+    #[serde(default, deserialize_with = "load_infohash_v2")]
     pub infohash_v2: String,
+    /// Position of the torrent in the queue, starting at 1.
+    ///
+    /// None when queueing is disabled, or the torrent is seeding.
+    #[serde(rename = "priority", deserialize_with = "load_queue_position")]
+    pub queue_position: Option<usize>,
+    #[serde(rename = "seq_dl")]
+    pub sequential_download: bool,
+    #[serde(rename = "f_l_piece_prio")]
+    pub first_last_piece_priority: bool,
+    pub force_start: bool,
+    pub super_seeding: bool,
+    #[serde(rename = "auto_tmm")]
+    pub auto_management: bool,
+    pub category: String,
+    /// Upload limit in bytes per second, 0 or -1 when unlimited
+    #[serde(rename = "up_limit")]
+    pub upload_limit: i64,
+    /// Download limit in bytes per second, 0 or -1 when unlimited
+    #[serde(rename = "dl_limit")]
+    pub download_limit: i64,
+    /// Ratio limit, -1 when unlimited and -2 when using the global limit
+    pub ratio_limit: f64,
+    /// Seeding time limit in minutes, -1 when unlimited and -2 when using the global limit
+    pub seeding_time_limit: i64,
 }
 
 impl ToTorrent for QBittorrentTorrent {
@@ -47,11 +81,28 @@ impl ToTorrent for QBittorrentTorrent {
 }
 
 impl QBittorrentTorrent {
-    fn hash(&self) -> InfoHash {
+    /// Returns the per-torrent settings, ignoring unlimited/global limits
+    pub fn to_settings(&self) -> TorrentSettings {
+        TorrentSettings {
+            category: Some(self.category.to_string()).filter(|category| !category.is_empty()),
+            upload_limit: u64::try_from(self.upload_limit).ok().filter(|l| *l > 0),
+            download_limit: u64::try_from(self.download_limit).ok().filter(|l| *l > 0),
+            ratio_limit: Some(self.ratio_limit).filter(|l| *l >= 0.0),
+            seeding_time_limit: u64::try_from(self.seeding_time_limit)
+                .ok()
+                .map(|minutes| Duration::from_secs(minutes.saturating_mul(60))),
+        }
+    }
+
+    /// Whether the torrent metadata is known (false for magnets still fetching metadata)
+    pub fn has_metadata(&self) -> bool {
+        self.size > 0 && !matches!(self.state.as_str(), "metaDL" | "forcedMetaDL")
+    }
+
+    pub(crate) fn hash(&self) -> InfoHash {
         match (&self.infohash_v1.is_empty(), &self.infohash_v2.is_empty()) {
-            (true, true) => {
-                panic!("API returned torrent without v1/v2 hash!");
-            }
+            // Before qBittorrent v4.4, only v1 torrents are supported and the ID is their hash
+            (true, true) => InfoHash::V1(self.id.to_string()),
             (true, false) => InfoHash::V2(self.infohash_v2.to_string()),
             (false, true) => InfoHash::V1(self.infohash_v1.to_string()),
             (false, false) => {
@@ -61,6 +112,46 @@ impl QBittorrentTorrent {
     }
 }
 
+fn load_id<'de, D>(deserializer: D) -> Result<TorrentID, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let id = String::deserialize(deserializer)?;
+    TorrentID::new(&id).map_err(serde::de::Error::custom)
+}
+
+fn load_infohash_v1<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    load_infohash(deserializer, 40)
+}
+
+fn load_infohash_v2<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    load_infohash(deserializer, 64)
+}
+
+/// Checks an infohash has the expected length and is hexadecimal, or is empty
+fn load_infohash<'de, D>(deserializer: D, len: usize) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let hash = String::deserialize(deserializer)?;
+    if hash.is_empty() {
+        return Ok(hash);
+    }
+
+    match InfoHash::new(&hash).map_err(serde::de::Error::custom)? {
+        InfoHash::V1(hash) | InfoHash::V2(hash) if hash.len() == len => Ok(hash),
+        _ => Err(serde::de::Error::custom(format!(
+            "Expected a {len} characters infohash, found {hash}"
+        ))),
+    }
+}
+
 fn load_tags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: Deserializer<'de>,
@@ -69,11 +160,113 @@ where
         .map(|s| s.split(',').map(|tag| tag.trim().to_string()).collect())
 }
 
+fn load_state<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|state| normalize_state(&state))
+}
+
+fn load_queue_position<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // QBittorrent returns -1 (or 0 in older releases) for non-queued torrents
+    i64::deserialize(deserializer).map(|pos| usize::try_from(pos).ok().filter(|pos| *pos > 0))
+}
+
+/// Deserializes from the 'trackers' endpoint of QBittorrent API
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QBittorrentTracker {
+    /// Announce URL, or `** [DHT] **`, `** [PeX] **` and `** [LSD] **` for peer sources
     pub url: String,
+    /// 0 disabled, 1 not contacted, 2 working, 3 updating, 4 not working
     pub status: usize,
     pub msg: String,
+    /// Tier of the tracker, -1 (or empty in older releases) for peer sources
+    #[serde(default = "no_tier", deserialize_with = "load_tier")]
+    pub tier: i64,
+    /// Statistics are -1 when unknown
+    #[serde(default = "unknown_count")]
+    pub num_seeds: i64,
+    #[serde(default = "unknown_count")]
+    pub num_peers: i64,
+    #[serde(default = "unknown_count")]
+    pub num_leeches: i64,
+    #[serde(default = "unknown_count")]
+    pub num_downloaded: i64,
+}
+
+impl QBittorrentTracker {
+    pub const DHT: &str = "** [DHT] **";
+    pub const PEX: &str = "** [PeX] **";
+    pub const LSD: &str = "** [LSD] **";
+
+    pub fn to_entry(&self) -> TrackerEntry {
+        let count = |count: i64| u64::try_from(count).ok();
+        TrackerEntry {
+            url: self.url.to_string(),
+            tier: u32::try_from(self.tier).ok(),
+            status: match self.status {
+                0 => TrackerStatus::Disabled,
+                1 => TrackerStatus::NotContacted,
+                2 => TrackerStatus::Working,
+                3 => TrackerStatus::Updating,
+                4 => TrackerStatus::NotWorking,
+                _ => TrackerStatus::Unknown,
+            },
+            seeds: count(self.num_seeds),
+            peers: count(self.num_peers),
+            leeches: count(self.num_leeches),
+            downloaded: count(self.num_downloaded),
+            message: self.msg.to_string(),
+        }
+    }
+}
+
+/// Sorts qBittorrent trackers into actual trackers and DHT/PeX/LSD peer sources
+pub(crate) fn tracker_details(trackers: &[QBittorrentTracker]) -> TrackerDetails {
+    let mut details = TrackerDetails::default();
+    for tracker in trackers {
+        let entry = tracker.to_entry();
+        match tracker.url.as_str() {
+            QBittorrentTracker::DHT => details.dht = Some(entry),
+            QBittorrentTracker::PEX => details.pex = Some(entry),
+            QBittorrentTracker::LSD => details.lsd = Some(entry),
+            _ => details.trackers.push(entry),
+        }
+    }
+    // Trackers without a tier come after the others
+    details
+        .trackers
+        .sort_by_key(|tracker| (tracker.tier.is_none(), tracker.tier));
+    details
+}
+
+fn no_tier() -> i64 {
+    -1
+}
+
+fn unknown_count() -> i64 {
+    -1
+}
+
+fn load_tier<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tier {
+        Number(i64),
+        Text(String),
+    }
+
+    // Older releases return an empty string for peer sources
+    Ok(match Tier::deserialize(deserializer)? {
+        Tier::Number(tier) => tier,
+        Tier::Text(tier) => tier.parse().unwrap_or(-1),
+    })
 }
 
 impl TryIntoTracker for QBittorrentTracker {
@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use crate::ApiError;
+use crate::api::{AddOption, Capabilities, Operation, Protocol};
+
+/// A version number, such as qBittorrent's `v5.1.2` or the Web API's `2.11.4`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = ApiError;
+
+    /// Parses a version, ignoring a leading `v` and any pre-release suffix (`5.1.0beta1`).
+    ///
+    /// Missing minor/patch numbers are zero.
+    fn from_str(s: &str) -> Result<Version, ApiError> {
+        let invalid = || ApiError::InvalidVersion {
+            version: s.to_string(),
+        };
+
+        let trimmed = s.trim();
+        let trimmed = trimmed.strip_prefix('v').unwrap_or(trimmed);
+        let mut numbers = trimmed.splitn(3, '.').map(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse::<u32>().map_err(|_| invalid())
+        });
+
+        let major = numbers.next().ok_or_else(invalid)??;
+        let minor = numbers.next().transpose()?.unwrap_or(0);
+        let patch = numbers.next().transpose()?.unwrap_or(0);
+        Ok(Version::new(major, minor, patch))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Versions of a qBittorrent daemon, and of its Web API
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QBittorrentVersion {
+    /// qBittorrent release, from `app/version`
+    pub app: Version,
+    /// Web API version, from `app/webapiVersion`
+    pub webapi: Version,
+}
+
+impl QBittorrentVersion {
+    /// Bittorrent v2 (and hybrid torrents) support
+    pub const V2_SINCE: Version = Version::new(4, 4, 0);
+    /// `torrents/export` endpoint
+    pub const EXPORT_SINCE_WEBAPI: Version = Version::new(2, 8, 14);
+    /// `contentLayout` field in `torrents/add`
+    pub const CONTENT_LAYOUT_SINCE: Version = Version::new(4, 3, 2);
+    /// `downloadPath` field in `torrents/add`
+    pub const DOWNLOAD_PATH_SINCE: Version = Version::new(4, 4, 0);
+    /// `stopCondition` and `addToTopOfQueue` fields in `torrents/add`
+    pub const STOP_CONDITION_SINCE: Version = Version::new(4, 5, 0);
+    /// Tracker tiers read from empty lines in `torrents/addTrackers`
+    pub const TRACKER_TIERS_SINCE: Version = Version::new(5, 0, 0);
+    /// Renaming of paused/resumed torrents to stopped/started (qBittorrent v5.0)
+    pub const STOPPED_SINCE_WEBAPI: Version = Version::new(2, 11, 0);
+
+    /// Whether torrents are stopped/started (qBittorrent v5), instead of paused/resumed (v4)
+    pub fn uses_stopped(&self) -> bool {
+        self.webapi >= Self::STOPPED_SINCE_WEBAPI
+    }
+
+    /// The `torrents/add` form field to add a torrent without starting it
+    pub fn stopped_field(&self) -> &'static str {
+        if self.uses_stopped() {
+            "stopped"
+        } else {
+            "paused"
+        }
+    }
+
+    /// The endpoint to stop (pause) torrents
+    pub fn stop_endpoint(&self) -> &'static str {
+        if self.uses_stopped() {
+            "torrents/stop"
+        } else {
+            "torrents/pause"
+        }
+    }
+
+    /// The endpoint to start (resume) torrents
+    pub fn start_endpoint(&self) -> &'static str {
+        if self.uses_stopped() {
+            "torrents/start"
+        } else {
+            "torrents/resume"
+        }
+    }
+
+    /// Returns what this qBittorrent release supports
+    pub fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::all();
+
+        if self.app < Self::V2_SINCE {
+            capabilities.protocols.remove(&Protocol::V2);
+            capabilities.protocols.remove(&Protocol::Hybrid);
+        }
+        if self.webapi < Self::EXPORT_SINCE_WEBAPI {
+            capabilities.operations.remove(&Operation::Export);
+        }
+        if self.app < Self::CONTENT_LAYOUT_SINCE {
+            capabilities.add_options.remove(&AddOption::ContentLayout);
+        }
+        if self.app < Self::DOWNLOAD_PATH_SINCE {
+            capabilities.add_options.remove(&AddOption::DownloadPath);
+        }
+        if self.app < Self::TRACKER_TIERS_SINCE {
+            capabilities.operations.remove(&Operation::AddTrackerTier);
+        }
+        if self.app < Self::STOP_CONDITION_SINCE {
+            capabilities.add_options.remove(&AddOption::StopCondition);
+            capabilities.add_options.remove(&AddOption::AddToTopOfQueue);
+        }
+
+        capabilities
+    }
+}
+
+/// Renames qBittorrent v4 torrent states (`pausedDL`, `pausedUP`) to their v5 equivalent
+/// (`stoppedDL`, `stoppedUP`), so all releases report the same states.
+pub fn normalize_state(state: &str) -> String {
+    match state.strip_prefix("paused") {
+        Some(rest) => format!("stopped{rest}"),
+        None => state.to_string(),
+    }
+}
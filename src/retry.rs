@@ -0,0 +1,219 @@
+//! Retry policy for transient failures, such as network errors or a restarting torrent client.
+//!
+//! Idempotent operations (listing torrents, trackers, files...) are retried according to the
+//! policy. Operations changing the state of the torrent client (adding or removing torrents...)
+//! are only retried when [RetryPolicy::retry_non_idempotent] is enabled, because a request may
+//! fail after the torrent client applied it.
+//!
+//! ```no_run
+//! use hightorrent_api::{Api, QBittorrentClient};
+//! use hightorrent_api::retry::RetryPolicy;
+//! use std::time::Duration;
+//!
+//! # async fn run() -> Result<(), hightorrent_api::ApiError> {
+//! let policy = RetryPolicy::new()
+//!     .max_attempts(5)
+//!     .initial_backoff(Duration::from_millis(500))
+//!     .on_attempt(|attempt| {
+//!         if let (Some(error), Some(delay)) = (attempt.error, attempt.retry_in) {
+//!             eprintln!("Attempt {} failed, retrying in {delay:?}: {error}", attempt.attempt);
+//!         }
+//!     });
+//!
+//! let client = QBittorrentClient::new_not_logged_in("http://localhost:8080", "admin", "adminadmin")?
+//!     .retry_policy(policy);
+//! client.do_login().await?;
+//! client.list().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use tokio::time::sleep;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ApiError;
+
+/// A predicate telling whether an error is worth retrying
+pub type RetryablePredicate = Arc<dyn Fn(&ApiError) -> bool + Send + Sync>;
+
+/// A hook called after every attempt
+pub type AttemptHook = Arc<dyn Fn(&RetryAttempt) + Send + Sync>;
+
+/// Reported to the [RetryPolicy::on_attempt] hook after every attempt
+#[derive(Debug)]
+pub struct RetryAttempt<'a> {
+    /// Number of this attempt, starting at 1
+    pub attempt: u32,
+    /// The error of this attempt, None when it succeeded
+    pub error: Option<&'a ApiError>,
+    /// Delay before the next attempt, None when there is no next attempt
+    pub retry_in: Option<Duration>,
+}
+
+/// How to retry operations failing with a transient error.
+///
+/// The default policy does not retry.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after every attempt
+    pub initial_backoff: Duration,
+    /// Maximum delay between two attempts
+    pub max_backoff: Duration,
+    /// Randomize delays (between half and all of the backoff), so many clients don't retry
+    /// at the same time
+    pub jitter: bool,
+    /// Also retry operations which change the state of the torrent client, such as adding torrents
+    pub retry_non_idempotent: bool,
+    pub retryable: RetryablePredicate,
+    pub on_attempt: Option<AttemptHook>,
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("retry_non_idempotent", &self.retry_non_idempotent)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            retry_non_idempotent: false,
+            retryable: Arc::new(is_transient),
+            on_attempt: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy making up to 3 attempts, starting with a 200ms backoff
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::default().max_attempts(3)
+    }
+
+    /// A policy which never retries
+    pub fn none() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> RetryPolicy {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> RetryPolicy {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn retry_non_idempotent(mut self, retry: bool) -> RetryPolicy {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    /// Decide which errors are retried, instead of [ApiError::is_retryable]
+    pub fn retryable<F: Fn(&ApiError) -> bool + Send + Sync + 'static>(
+        mut self,
+        f: F,
+    ) -> RetryPolicy {
+        self.retryable = Arc::new(f);
+        self
+    }
+
+    /// Report every attempt, for example for logging
+    pub fn on_attempt<F: Fn(&RetryAttempt) + Send + Sync + 'static>(mut self, f: F) -> RetryPolicy {
+        self.on_attempt = Some(Arc::new(f));
+        self
+    }
+
+    /// Returns the delay before the given retry (starting at 1)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            let half = backoff / 2;
+            half + half.mul_f64(random_fraction())
+        } else {
+            backoff
+        }
+    }
+
+    /// Runs the operation until it succeeds, fails with a non-retryable error, or the attempts
+    /// are exhausted.
+    pub async fn run<T, F, Fut>(&self, idempotent: bool, operation: F) -> Result<T, ApiError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let max_attempts = if idempotent || self.retry_non_idempotent {
+            self.max_attempts
+        } else {
+            1
+        };
+
+        let mut attempt = 1;
+        loop {
+            let result = operation().await;
+
+            let retry_in = match &result {
+                Err(e) if attempt < max_attempts && (self.retryable)(e) => {
+                    Some(self.backoff(attempt))
+                }
+                _ => None,
+            };
+
+            if let Some(hook) = &self.on_attempt {
+                hook(&RetryAttempt {
+                    attempt,
+                    error: result.as_ref().err(),
+                    retry_in,
+                });
+            }
+
+            match retry_in {
+                Some(delay) => sleep(delay).await,
+                None => return result,
+            }
+            attempt += 1;
+        }
+    }
+}
+
+/// Whether the error may not happen again, see [ApiError::is_retryable]
+pub fn is_transient(error: &ApiError) -> bool {
+    error.is_retryable()
+}
+
+/// Returns a random number between 0 and 1, without depending on a random number generator
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
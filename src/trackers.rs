@@ -0,0 +1,196 @@
+//! Rewrite tracker URLs across many torrents, for example when a tracker changes its announce
+//! domain.
+//!
+//! Trackers are edited in place with [Api::edit_tracker](crate::Api::edit_tracker), so they keep
+//! their tier.
+//!
+//! ```no_run
+//! use hightorrent_api::{Api, QBittorrentClient};
+//! use hightorrent_api::trackers::{TrackerReplacement, replace_trackers};
+//!
+//! # async fn run() -> Result<(), hightorrent_api::ApiError> {
+//! let api = QBittorrentClient::login("http://localhost:8080", "admin", "adminadmin").await?;
+//!
+//! let replacement = TrackerReplacement::new("tracker.old.example", "tracker.new.example")
+//!     .dry_run(true);
+//!
+//! let report = replace_trackers(&api, &replacement).await?;
+//! for entry in report.entries {
+//!     for edit in entry.edits {
+//!         println!("{}: {} -> {}", entry.hash, edit.from, edit.to);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use hightorrent::{InfoHash, MultiTarget, SingleTarget, Torrent};
+
+use crate::{Api, ApiError};
+
+/// Returns the new URL for a tracker URL, or `None` to leave the tracker untouched
+pub type TrackerRewrite = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Settings for replacing tracker URLs
+pub struct TrackerReplacement {
+    pub target: MultiTarget,
+    pub rewrite: TrackerRewrite,
+    pub dry_run: bool,
+}
+
+impl TrackerReplacement {
+    /// Replaces every occurrence of `pattern` in tracker URLs with `replacement`.
+    ///
+    /// An empty pattern matches no tracker.
+    pub fn new(pattern: &str, replacement: &str) -> TrackerReplacement {
+        let pattern = pattern.to_string();
+        let replacement = replacement.to_string();
+        TrackerReplacement::with(move |url| {
+            if pattern.is_empty() || !url.contains(&pattern) {
+                return None;
+            }
+            Some(url.replace(&pattern, &replacement))
+        })
+    }
+
+    /// Rewrites tracker URLs with a custom function, returning `None` for trackers which should
+    /// be left untouched
+    pub fn with<F: Fn(&str) -> Option<String> + Send + Sync + 'static>(f: F) -> TrackerReplacement {
+        TrackerReplacement {
+            target: MultiTarget::All,
+            rewrite: Box::new(f),
+            dry_run: false,
+        }
+    }
+
+    /// Only replace trackers of the targeted torrents
+    pub fn target(mut self, target: MultiTarget) -> TrackerReplacement {
+        self.target = target;
+        self
+    }
+
+    /// Report which trackers would be replaced, without changing anything
+    pub fn dry_run(mut self, d: bool) -> TrackerReplacement {
+        self.dry_run = d;
+        self
+    }
+
+    /// Returns the new URL for a tracker, if it should be replaced
+    pub fn rewrite(&self, url: &str) -> Option<String> {
+        (self.rewrite)(url).filter(|new| new != url)
+    }
+}
+
+/// The outcome of a tracker replacement, for every targeted torrent
+#[derive(Debug)]
+pub struct TrackerReplacementReport {
+    pub entries: Vec<TrackerReplacementEntry>,
+}
+
+impl TrackerReplacementReport {
+    /// Number of trackers which were replaced (or would be, for a dry run)
+    pub fn edits(&self) -> usize {
+        self.entries.iter().map(|entry| entry.edits.len()).sum()
+    }
+}
+
+#[derive(Debug)]
+pub struct TrackerReplacementEntry {
+    pub hash: InfoHash,
+    pub name: String,
+    /// Trackers replaced (or which would be, for a dry run). When the torrent failed, only the
+    /// trackers replaced before the failure.
+    pub edits: Vec<TrackerEdit>,
+    pub result: TrackerReplacementResult,
+}
+
+/// A tracker URL replaced on a torrent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackerEdit {
+    pub from: String,
+    pub to: String,
+    /// Tier of the tracker, which is kept by the replacement
+    pub tier: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum TrackerReplacementResult {
+    /// Trackers would be replaced, but this is a dry run
+    DryRun,
+    Replaced,
+    /// No tracker of the torrent matched
+    Unchanged,
+    Failed(ApiError),
+}
+
+/// Replaces tracker URLs on the targeted torrents.
+///
+/// Torrents which fail do not stop the replacement; their error is in the report.
+pub async fn replace_trackers<A: Api + ?Sized>(
+    api: &A,
+    replacement: &TrackerReplacement,
+) -> Result<TrackerReplacementReport, ApiError> {
+    let torrents = match &replacement.target {
+        MultiTarget::All => api.list().await?.to_vec(),
+        MultiTarget::Hash(single_target) => match api.get(single_target).await? {
+            Some(torrent) => vec![torrent],
+            None => {
+                return Err(ApiError::MissingTorrent {
+                    hash: single_target.to_string(),
+                });
+            }
+        },
+    };
+
+    let mut entries = Vec::with_capacity(torrents.len());
+    for torrent in torrents {
+        let mut entry = TrackerReplacementEntry {
+            hash: torrent.hash.clone(),
+            name: torrent.name.clone(),
+            edits: Vec::new(),
+            result: TrackerReplacementResult::Unchanged,
+        };
+
+        if let Err(e) = replace_torrent(api, replacement, &torrent, &mut entry.edits).await {
+            entry.result = TrackerReplacementResult::Failed(e);
+        } else if !entry.edits.is_empty() {
+            entry.result = if replacement.dry_run {
+                TrackerReplacementResult::DryRun
+            } else {
+                TrackerReplacementResult::Replaced
+            };
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(TrackerReplacementReport { entries })
+}
+
+/// Replaces the matching trackers of a single torrent, recording every replaced tracker
+async fn replace_torrent<A: Api + ?Sized>(
+    api: &A,
+    replacement: &TrackerReplacement,
+    torrent: &Torrent,
+    edits: &mut Vec<TrackerEdit>,
+) -> Result<(), ApiError> {
+    let target = SingleTarget::from(&torrent.hash);
+    let details = api.get_tracker_details(&target).await?;
+
+    for tracker in details.trackers {
+        let Some(to) = replacement.rewrite(&tracker.url) else {
+            continue;
+        };
+
+        if !replacement.dry_run {
+            api.edit_tracker(&target, &tracker.url, &to).await?;
+        }
+        edits.push(TrackerEdit {
+            from: tracker.url,
+            to,
+            tier: tracker.tier,
+        });
+    }
+
+    Ok(())
+}
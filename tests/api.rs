@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use hightorrent::{InfoHash, SingleTarget, Torrent, TorrentContent, TorrentList, Tracker};
+use hightorrent_api::api::{AddBuilder, AddSource, AddedState, ApiAdd, Capabilities, Operation};
+use hightorrent_api::{Api, ApiError};
+
+/// A backend only implementing the required operations, without any torrent
+struct MinimalBackend;
+
+#[async_trait]
+impl<'a> ApiAdd<'a> for MinimalBackend {
+    async fn api_add_send(&self, _add: AddBuilder<'a, AddSource>) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn api_add_state(&self, _hash: &InfoHash) -> Result<Option<AddedState>, ApiError> {
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl Api for MinimalBackend {
+    async fn login(_host: &str, _user: &str, _password: &str) -> Result<Self, ApiError> {
+        Ok(MinimalBackend)
+    }
+
+    fn host(&self) -> String {
+        String::new()
+    }
+
+    fn user(&self) -> String {
+        String::new()
+    }
+
+    fn password(&self) -> String {
+        String::new()
+    }
+
+    async fn list(&self) -> Result<TorrentList, ApiError> {
+        Ok(TorrentList::new())
+    }
+
+    async fn get(&self, _hash: &SingleTarget) -> Result<Option<Torrent>, ApiError> {
+        Ok(None)
+    }
+
+    async fn remove(&self, _hash: &SingleTarget, _delete_files: bool) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn get_trackers(&self, _hash: &SingleTarget) -> Result<Vec<Tracker>, ApiError> {
+        Ok(Vec::new())
+    }
+
+    async fn add_tracker(&self, _hash: &SingleTarget, _tracker: &str) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn remove_tracker(&self, _hash: &SingleTarget, _tracker: &str) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn get_files(&self, _hash: &SingleTarget) -> Result<Vec<TorrentContent>, ApiError> {
+        Ok(Vec::new())
+    }
+}
+
+#[tokio::test]
+async fn optional_operations() -> Result<(), ApiError> {
+    let api = MinimalBackend;
+    let target = SingleTarget::new("2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c").unwrap();
+
+    // Optional operations are reported as unsupported, and fail when called
+    let capabilities = api.capabilities().await?;
+    assert_eq!(capabilities, Capabilities::required());
+    for operation in [
+        Operation::Export,
+        Operation::GetSettings,
+        Operation::GetTrackerDetails,
+        Operation::AddTrackerTier,
+        Operation::EditTracker,
+    ] {
+        assert!(!capabilities.supports(operation));
+    }
+    assert!(capabilities.supports(Operation::List));
+
+    assert!(matches!(
+        api.export(&target).await,
+        Err(ApiError::UnsupportedOperation { .. })
+    ));
+    assert!(matches!(
+        api.get_settings(&target).await,
+        Err(ApiError::UnsupportedOperation { .. })
+    ));
+    assert!(matches!(
+        api.get_tracker_details(&target).await,
+        Err(ApiError::UnsupportedOperation { .. })
+    ));
+    assert!(matches!(
+        api.add_tracker_tier(&target, "http://a/announce", 1).await,
+        Err(ApiError::UnsupportedOperation { endpoint, .. }) if endpoint == "AddTrackerTier"
+    ));
+    assert!(matches!(
+        api.edit_tracker(&target, "http://a/announce", "http://b/announce")
+            .await,
+        Err(ApiError::UnsupportedOperation { endpoint, .. }) if endpoint == "EditTracker"
+    ));
+
+    // Through dynamic dispatch too
+    let api: Box<dyn Api> = Box::new(api);
+    assert_eq!(api.capabilities().await?, Capabilities::required());
+    assert!(matches!(
+        api.export(&target).await,
+        Err(ApiError::UnsupportedOperation { .. })
+    ));
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Fixtures shared by the qBittorrent integration tests: test torrents, and a fake
+//! qBittorrent daemon for tests which don't need a running one.
+#![allow(dead_code)]
+
+use hightorrent::MultiTarget;
+use hightorrent_api::qbittorrent::QBittorrentTorrent;
+use hightorrent_api::{ApiError, QBittorrentClient};
+
+use std::sync::Arc;
+
+pub static V2_MAGNET: &str = "magnet:?xt=urn:btmh:1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e&dn=bittorrent-v2-test";
+// static V2_TORRENT: &[u8] = include_bytes!("../bittorrent-v2-test.torrent");
+pub static V2_V2HASH: &str = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+// static V2_ID: &str = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa";
+// static V2_NAME: &str = "bittorrent-v2-test";
+
+// static HYBRID_MAGNET: &str = "magnet:?xt=urn:btih:631a31dd0a46257d5078c0dee4e66e26f73e42ac&xt=urn:btmh:1220d8dd32ac93357c368556af3ac1d95c9d76bd0dff6fa9833ecdac3d53134efabb&dn=bittorrent-v1-v2-test";
+// static HYBRID_TORRENT: &[u8] = include_bytes!("../bittorrent-v2-hybrid-test.torrent");
+// static HYBRID_V2HASH: &str = "d8dd32ac93357c368556af3ac1d95c9d76bd0dff6fa9833ecdac3d53134efabb";
+// static HYBRID_V1HASH: &str = "631a31dd0a46257d5078c0dee4e66e26f73e42ac";
+// static HYBRID_ID: &str = "d8dd32ac93357c368556af3ac1d95c9d76bd0dff";
+// static HYBRID_NAME: &str = "bittorrent-v1-v2-hybrid-test";
+
+pub static V1_MAGNET: &str = "magnet:?xt=urn:btih:2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c&dn=tails-amd64-5.6-img&tr=udp%3a%2f%2ftracker.torrent.eu.org%3a451&tr=udp%3a%2f%2ftracker.coppersurfer.tk%3a6969";
+pub static V1_TORRENT: &[u8] = include_bytes!("../tails-amd64-5.6.img.torrent");
+pub static V1_V1HASH: &str = "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c";
+pub static V1_ID: &str = "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c";
+pub static V1_NAME: &str = "tails-amd64-5.6-img";
+
+/// The flags set by `QBittorrentClient::set_*`, with their `torrents/info` field and endpoint
+pub static FLAGS: [(&str, &str); 5] = [
+    ("seq_dl", "torrents/togglesequentialdownload"),
+    ("f_l_piece_prio", "torrents/togglefirstlastpieceprio"),
+    ("force_start", "torrents/setforcestart"),
+    ("super_seeding", "torrents/setsuperseeding"),
+    ("auto_tmm", "torrents/setautomanagement"),
+];
+
+/// Sets one of the `FLAGS`
+pub async fn set_flag(
+    api: &QBittorrentClient,
+    flag: usize,
+    target: &MultiTarget,
+    enabled: bool,
+) -> Result<(), ApiError> {
+    match flag {
+        0 => api.set_sequential_download(target, enabled).await,
+        1 => api.set_first_last_piece_priority(target, enabled).await,
+        2 => api.set_force_start(target, enabled).await,
+        3 => api.set_super_seeding(target, enabled).await,
+        _ => api.set_auto_management(target, enabled).await,
+    }
+}
+
+/// Reads one of the `FLAGS`
+pub fn get_flag(torrent: &QBittorrentTorrent, flag: usize) -> bool {
+    match flag {
+        0 => torrent.sequential_download,
+        1 => torrent.first_last_piece_priority,
+        2 => torrent.force_start,
+        3 => torrent.super_seeding,
+        _ => torrent.auto_management,
+    }
+}
+
+/// Requests received by a fake daemon, lowercased
+pub type Requests = Arc<std::sync::Mutex<Vec<String>>>;
+
+/// Starts a fake qBittorrent daemon answering every request (lowercased) with the status and
+/// body returned by the handler, and returns its URL along with the received requests
+pub fn fake_server<F>(handler: F) -> (String, Requests)
+where
+    F: Fn(&str) -> (&'static str, String) + Send + 'static,
+{
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let request = read_request(&mut stream);
+            received.lock().unwrap().push(request.clone());
+            let (status, body) = handler(&request);
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = std::io::Write::write_all(&mut stream, response.as_bytes());
+        }
+    });
+    (host, requests)
+}
+
+/// Reads an HTTP request with its body, when it has a Content-Length
+pub fn read_request(stream: &mut std::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let read = std::io::Read::read(stream, &mut buffer).unwrap_or(0);
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+
+        let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+        let length = headers
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .and_then(|length| length.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if request.len() >= end + 4 + length {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&request).to_lowercase()
+}
+
+/// Starts a fake qBittorrent daemon of the given versions, listing the given torrents (without
+/// trackers nor metadata) and accepting all other requests
+pub fn fake_qbittorrent(
+    app: &'static str,
+    webapi: &'static str,
+    torrents: serde_json::Value,
+) -> (String, Requests) {
+    fake_server(move |request| {
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        let path = path.split('?').next().unwrap_or_default();
+        let body = match path {
+            "/api/v2/app/version" => app.to_string(),
+            "/api/v2/app/webapiversion" => webapi.to_string(),
+            "/api/v2/torrents/info" => torrents.to_string(),
+            "/api/v2/torrents/trackers" => "[]".to_string(),
+            "/api/v2/torrents/export" => return ("409 Conflict", String::new()),
+            "/api/v2/torrents/add" => "Ok.".to_string(),
+            _ => String::new(),
+        };
+        ("200 OK", body)
+    })
+}
+
+/// Returns the value of a multipart form field in a request
+pub fn form_field(request: &str, name: &str) -> Option<String> {
+    let pattern = format!("name=\"{name}\"\r\n\r\n");
+    let start = request.find(&pattern)? + pattern.len();
+    let end = request[start..].find("\r\n")?;
+    Some(request[start..start + end].to_string())
+}
+
+/// A torrent as returned by the `torrents/info` endpoint, with all flags disabled
+pub fn fake_torrent(id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": id,
+        "save_path": "/downloads",
+        "added_on": 1700000000,
+        "completion_on": -1,
+        "progress": 0.0,
+        "total_size": 1234567,
+        "state": "stoppedDL",
+        "tags": "",
+        "hash": id,
+        "infohash_v1": id,
+        "infohash_v2": "",
+        "priority": 1,
+        "seq_dl": false,
+        "f_l_piece_prio": false,
+        "force_start": false,
+        "super_seeding": false,
+        "auto_tmm": false,
+        "category": "",
+        "up_limit": -1,
+        "dl_limit": -1,
+        "ratio_limit": -2,
+        "seeding_time_limit": -2,
+    })
+}
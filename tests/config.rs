@@ -0,0 +1,119 @@
+#![cfg(feature = "config")]
+
+use hightorrent_api::ApiError;
+use hightorrent_api::config::{Backend, Config};
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+static CONFIG: &str = r#"
+[profiles.default]
+url = "http://localhost:8080"
+user = "admin"
+password = "adminadmin"
+timeout = 30
+
+[profiles.seedbox]
+backend = "qbittorrent"
+url = "https://seedbox.lan"
+password_file = "/run/secrets/seedbox"
+
+[profiles.seedbox.tls]
+accept_invalid_certs = true
+
+[profiles.nas]
+url = "https://nas.lan"
+
+[profiles.nas.tls]
+fingerprint = "not a fingerprint"
+client_cert = "/etc/ssl/hightorrent.pem"
+"#;
+
+fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn parse_profiles() -> Result<(), ApiError> {
+    let config = Config::from_str(CONFIG)?;
+
+    let default = config.profile("default")?;
+    assert_eq!(default.backend, Backend::QBittorrent);
+    assert_eq!(default.url.as_deref(), Some("http://localhost:8080"));
+    assert_eq!(default.password()?, "adminadmin");
+    assert_eq!(default.timeout, Some(Duration::from_secs(30)));
+    assert_eq!(default.connect_timeout, None);
+
+    let seedbox = config.profile("seedbox")?;
+    assert_eq!(
+        seedbox.password_file,
+        Some(PathBuf::from("/run/secrets/seedbox"))
+    );
+    assert!(seedbox.tls.accept_invalid_certs);
+
+    assert!(matches!(
+        config.profile("missing"),
+        Err(ApiError::MissingProfile { .. })
+    ));
+    assert!(matches!(
+        Config::from_str("[profiles.default]\nhost = \"http://localhost:8080\""),
+        Err(ApiError::InvalidConfig { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn env_overrides() -> Result<(), ApiError> {
+    let mut config = Config::from_str(CONFIG)?;
+    config.merge_env(vars(&[
+        ("HIGHTORRENT_PROFILE_DEFAULT_PASSWORD", "secret"),
+        ("HIGHTORRENT_PROFILE_DEFAULT_CONNECT_TIMEOUT", "5"),
+        ("HIGHTORRENT_PROFILE_SEEDBOX_PASSWORD_FILE", "/tmp/password"),
+        ("HIGHTORRENT_PROFILE_MY_BOX_URL", "http://mybox:8080"),
+        ("HIGHTORRENT_UNRELATED", "ignored"),
+    ]))?;
+
+    let default = config.profile("default")?;
+    assert_eq!(default.password()?, "secret");
+    assert_eq!(default.timeout, Some(Duration::from_secs(30)));
+    assert_eq!(default.connect_timeout, Some(Duration::from_secs(5)));
+
+    let seedbox = config.profile("seedbox")?;
+    assert_eq!(seedbox.password_file, Some(PathBuf::from("/tmp/password")));
+    assert_eq!(seedbox.password, None);
+
+    let my_box = config.profile("my_box")?;
+    assert_eq!(my_box.url.as_deref(), Some("http://mybox:8080"));
+
+    assert!(matches!(
+        config.merge_env(vars(&[("HIGHTORRENT_PROFILE_DEFAULT_TIMEOUT", "soon")])),
+        Err(ApiError::InvalidConfig { .. })
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "qbittorrent")]
+#[test]
+fn tls_settings() -> Result<(), ApiError> {
+    let config = Config::from_str(CONFIG)?;
+    let nas = config.profile("nas")?;
+    assert_eq!(nas.tls.fingerprint.as_deref(), Some("not a fingerprint"));
+
+    // The client key is missing
+    assert!(matches!(
+        nas.qbittorrent_builder(),
+        Err(ApiError::InvalidConfig { .. })
+    ));
+
+    // The fingerprint is invalid
+    let mut nas = nas.clone();
+    nas.tls.client_cert = None;
+    assert!(matches!(
+        nas.qbittorrent_builder()?.build(),
+        Err(ApiError::ClientInit { .. })
+    ));
+    Ok(())
+}
@@ -0,0 +1,192 @@
+#![cfg(feature = "qbittorrent")]
+
+//! Feeds arbitrary JSON to the qBittorrent deserializers, to make sure malformed responses
+//! return an error instead of panicking.
+
+use hightorrent::{InfoHash, ToTorrent, ToTorrentContent, TryIntoTracker};
+use hightorrent_api::api::TrackerStatus;
+use hightorrent_api::qbittorrent::{
+    QBittorrentTorrent, QBittorrentTorrentContent, QBittorrentTracker, Version,
+};
+use proptest::prelude::*;
+use serde_json::{Map, Value, json};
+
+/// A torrent as returned by the `torrents/info` endpoint
+fn torrent() -> Value {
+    json!({
+        "name": "tails-amd64-5.6-img",
+        "save_path": "/downloads",
+        "added_on": 1700000000,
+        "completion_on": -1,
+        "progress": 0.5,
+        "total_size": 1234567,
+        "state": "pausedDL",
+        "tags": "linux, iso",
+        "hash": "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c",
+        "infohash_v1": "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c",
+        "infohash_v2": "",
+        "priority": 1,
+        "seq_dl": false,
+        "f_l_piece_prio": false,
+        "force_start": false,
+        "super_seeding": false,
+        "auto_tmm": true,
+        "category": "",
+        "up_limit": -1,
+        "dl_limit": 0,
+        "ratio_limit": -2,
+        "seeding_time_limit": 9223372036854775807i64,
+    })
+}
+
+fn arbitrary_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<u64>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        ".*".prop_map(Value::from),
+        "[0-9a-fA-F]{0,70}".prop_map(Value::from),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::from),
+            prop::collection::hash_map(".*", inner, 0..8)
+                .prop_map(|map| Value::Object(map.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+/// A valid torrent with some fields removed or replaced by arbitrary JSON
+fn mutated_torrent() -> impl Strategy<Value = Value> {
+    let fields: Vec<String> = torrent().as_object().unwrap().keys().cloned().collect();
+    prop::collection::vec(
+        (
+            prop::sample::select(fields),
+            prop::option::of(arbitrary_json()),
+        ),
+        0..6,
+    )
+    .prop_map(|mutations| {
+        let mut torrent = torrent();
+        let object = torrent.as_object_mut().unwrap();
+        for (field, value) in mutations {
+            match value {
+                Some(value) => object.insert(field, value),
+                None => object.remove(&field),
+            };
+        }
+        torrent
+    })
+}
+
+/// Deserializes the response of every endpoint, using the result when it's valid
+fn deserialize_all(json: &Value) {
+    let bytes = serde_json::to_vec(json).unwrap();
+
+    if let Ok(torrent) = serde_json::from_slice::<QBittorrentTorrent>(&bytes) {
+        torrent.to_torrent();
+        torrent.to_settings();
+        torrent.has_metadata();
+    }
+    if let Ok(torrents) = serde_json::from_slice::<Vec<QBittorrentTorrent>>(&bytes) {
+        for torrent in torrents {
+            torrent.to_torrent();
+        }
+    }
+    if let Ok(trackers) = serde_json::from_slice::<Vec<QBittorrentTracker>>(&bytes) {
+        for tracker in trackers {
+            let _ = tracker.try_into_tracker();
+            tracker.to_entry();
+        }
+    }
+    if let Ok(files) = serde_json::from_slice::<Vec<QBittorrentTorrentContent>>(&bytes) {
+        for file in files {
+            file.to_torrent_content();
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_responses(json in arbitrary_json()) {
+        deserialize_all(&json);
+        deserialize_all(&Value::from(vec![json]));
+    }
+
+    #[test]
+    fn mutated_torrents(json in mutated_torrent()) {
+        deserialize_all(&json);
+        deserialize_all(&Value::from(vec![json]));
+    }
+
+    #[test]
+    fn arbitrary_versions(version in ".*") {
+        let _ = version.parse::<Version>();
+    }
+}
+
+#[test]
+fn torrent_hashes() {
+    let parse = |fields: Value| {
+        let mut torrent = torrent();
+        for (field, value) in fields.as_object().unwrap() {
+            match value {
+                Value::Null => torrent.as_object_mut().unwrap().remove(field),
+                value => torrent
+                    .as_object_mut()
+                    .unwrap()
+                    .insert(field.to_string(), value.clone()),
+            };
+        }
+        serde_json::from_value::<QBittorrentTorrent>(torrent)
+    };
+
+    // Before qBittorrent v4.4, there are no infohash fields
+    let torrent = parse(json!({"infohash_v1": null, "infohash_v2": null})).unwrap();
+    assert_eq!(
+        torrent.to_torrent().hash,
+        InfoHash::V1("2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c".to_string())
+    );
+
+    // Invalid hashes are errors
+    assert!(parse(json!({"hash": "not a hash"})).is_err());
+    assert!(parse(json!({"infohash_v1": "2c6e17017f6bb87125b2ba98c56a67f8ffe7e0"})).is_err());
+    assert!(parse(json!({"infohash_v2": "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c"})).is_err());
+}
+
+#[test]
+fn tracker_entries() {
+    let trackers: Vec<QBittorrentTracker> = serde_json::from_value(json!([
+        {"url": "** [DHT] **", "status": 2, "msg": "", "tier": -1, "num_peers": 12,
+            "num_seeds": -1, "num_leeches": -1, "num_downloaded": -1},
+        // Before qBittorrent v4.5, peer sources have an empty tier
+        {"url": "** [PeX] **", "status": 0, "msg": "", "tier": ""},
+        {"url": "udp://tracker.example:1337/announce", "status": 4, "msg": "Timed out",
+            "tier": 1, "num_peers": 0, "num_seeds": 3, "num_leeches": 2, "num_downloaded": 40},
+        {"url": "https://tracker.example/announce", "status": 7, "msg": "", "tier": 0},
+    ]))
+    .unwrap();
+
+    let dht = trackers[0].to_entry();
+    assert_eq!(dht.tier, None);
+    assert_eq!(dht.status, TrackerStatus::Working);
+    assert_eq!((dht.peers, dht.seeds), (Some(12), None));
+
+    let pex = trackers[1].to_entry();
+    assert_eq!(pex.tier, None);
+    assert_eq!(pex.status, TrackerStatus::Disabled);
+
+    let udp = trackers[2].to_entry();
+    assert_eq!(udp.tier, Some(1));
+    assert_eq!(udp.status, TrackerStatus::NotWorking);
+    assert_eq!(
+        (udp.seeds, udp.peers, udp.leeches, udp.downloaded),
+        (Some(3), Some(0), Some(2), Some(40))
+    );
+    assert_eq!(udp.message, "Timed out");
+
+    // Statuses from newer releases are not errors
+    assert_eq!(trackers[3].to_entry().status, TrackerStatus::Unknown);
+}
@@ -1,33 +1,28 @@
-use hightorrent::SingleTarget;
+//! Tests against a running qBittorrent instance on localhost:8080, logged in as admin.
+
+use hightorrent::{MultiTarget, SingleTarget};
+use hightorrent_api::api::{
+    AddOption, AddSource, BatchAddResult, ContentLayout, Operation, Protocol, SeedingLimit,
+};
+use hightorrent_api::backup::{RestoreOptions, RestoreResult, backup, restore};
+use hightorrent_api::migrate::{Migration, MigrationResult, migrate};
+use hightorrent_api::qbittorrent::{FileSessionStore, QBittorrentVersion, SessionStore};
+use hightorrent_api::trackers::{TrackerReplacement, TrackerReplacementResult, replace_trackers};
 use hightorrent_api::{Api, ApiError, QBittorrentClient};
-use tokio::sync::OnceCell;
+use tokio::sync::{Mutex, MutexGuard, OnceCell};
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
-use std::sync::{Mutex, MutexGuard};
+mod common;
+use common::*;
 
 // We wrap the API client in a mutex, and ensure we only use one client.
 // We don't have hundreds of torrent files to test with,
 // so we need to make sure operations are not executed in parallel.
 static LOCK: OnceCell<Mutex<QBittorrentClient>> = OnceCell::const_new();
 
-// static V2_MAGNET: &str = "magnet:?xt=urn:btmh:1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e&dn=bittorrent-v2-test";
-// static V2_TORRENT: &[u8] = include_bytes!("bittorrent-v2-test.torrent");
-// static V2_V2HASH: &str = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
-// static V2_ID: &str = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa";
-// static V2_NAME: &str = "bittorrent-v2-test";
-
-// static HYBRID_MAGNET: &str = "magnet:?xt=urn:btih:631a31dd0a46257d5078c0dee4e66e26f73e42ac&xt=urn:btmh:1220d8dd32ac93357c368556af3ac1d95c9d76bd0dff6fa9833ecdac3d53134efabb&dn=bittorrent-v1-v2-test";
-// static HYBRID_TORRENT: &[u8] = include_bytes!("bittorrent-v2-hybrid-test.torrent");
-// static HYBRID_V2HASH: &str = "d8dd32ac93357c368556af3ac1d95c9d76bd0dff6fa9833ecdac3d53134efabb";
-// static HYBRID_V1HASH: &str = "631a31dd0a46257d5078c0dee4e66e26f73e42ac";
-// static HYBRID_ID: &str = "d8dd32ac93357c368556af3ac1d95c9d76bd0dff";
-// static HYBRID_NAME: &str = "bittorrent-v1-v2-hybrid-test";
-
-static V1_MAGNET: &str = "magnet:?xt=urn:btih:2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c&dn=tails-amd64-5.6-img&tr=udp%3a%2f%2ftracker.torrent.eu.org%3a451&tr=udp%3a%2f%2ftracker.coppersurfer.tk%3a6969";
-// static V1_TORRENT: &[u8] = include_bytes!("tails-amd64-5.6.img.torrent");
-static V1_V1HASH: &str = "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c";
-static V1_ID: &str = "2c6e17017f6bb87125b2ba98c56a67f8ffe7e02c";
-static V1_NAME: &str = "tails-amd64-5.6-img";
-
 async fn client() -> MutexGuard<'static, QBittorrentClient> {
     LOCK.get_or_init(|| async {
         Mutex::new(
@@ -38,7 +33,7 @@ async fn client() -> MutexGuard<'static, QBittorrentClient> {
     })
     .await
     .lock()
-    .unwrap()
+    .await
 }
 
 #[tokio::test]
@@ -69,7 +64,8 @@ async fn magnet_v1() -> Result<(), ApiError> {
     assert!(entry.is_none());
 
     // Add torrent
-    api.add().magnet(V1_MAGNET).paused(true).send().await?;
+    let hash = api.add().magnet(V1_MAGNET).paused(true).send().await?;
+    assert_eq!(hash.unwrap().id().as_str(), V1_ID);
 
     // Check torrent does exist now
     let list = api.list().await?;
@@ -93,3 +89,443 @@ async fn magnet_v1() -> Result<(), ApiError> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn torrent_bytes_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    // The torrent may already be there from another test
+    if api.get(&target).await?.is_some() {
+        api.remove(&target, false).await?;
+    }
+    assert!(api.get(&target).await?.is_none());
+
+    // Add torrent from memory
+    api.add()
+        .torrent_bytes(V1_TORRENT.to_vec())
+        .paused(true)
+        .wait(Duration::from_secs(5))
+        .send()
+        .await?;
+
+    let entry = api.get(&target).await?;
+    assert!(entry.is_some());
+    assert_eq!(entry.unwrap().hash.id().as_str(), V1_ID);
+
+    // Export it back
+    let exported = api.export_parsed(&target).await?;
+    assert_eq!(exported.id().as_str(), V1_ID);
+
+    // Adding it again is reported as a duplicate
+    let res = api.add().torrent_bytes(V1_TORRENT.to_vec()).send().await;
+    assert!(matches!(res, Err(ApiError::DuplicateTorrent { .. })));
+
+    // Remove torrent
+    api.remove(&target, true).await?;
+    assert!(api.get(&target).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn torrent_file_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    // The torrent may already be there from another test
+    if api.get(&target).await?.is_some() {
+        api.remove(&target, false).await?;
+    }
+
+    // Add torrent from disk, streamed to qBittorrent
+    let hash = api
+        .add()
+        .torrent_file(Path::new("tests/tails-amd64-5.6.img.torrent"))
+        .paused(true)
+        .send()
+        .await?;
+    assert_eq!(hash.unwrap().id().as_str(), V1_ID);
+    assert!(api.get(&target).await?.is_some());
+
+    // Adding it again is detected as a duplicate
+    assert!(matches!(
+        api.add()
+            .torrent_file(Path::new("tests/tails-amd64-5.6.img.torrent"))
+            .send()
+            .await,
+        Err(ApiError::DuplicateTorrent { .. })
+    ));
+
+    // Remove torrent
+    api.remove(&target, true).await?;
+    assert!(api.get(&target).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    // The torrent may already be there from another test
+    if api.get(&target).await?.is_some() {
+        api.remove(&target, false).await?;
+    }
+
+    // The same torrent twice, the second one is a duplicate
+    let results = api
+        .add()
+        .paused(true)
+        .batch(vec![
+            AddSource::magnet(V1_MAGNET),
+            AddSource::torrent_bytes(V1_TORRENT.to_vec()),
+            AddSource::torrent_bytes(b"not a torrent".to_vec()),
+        ])
+        .send()
+        .await?;
+    assert!(matches!(results[0], BatchAddResult::Added(Some(_))));
+    assert!(matches!(results[1], BatchAddResult::Duplicate(_)));
+    assert!(matches!(results[2], BatchAddResult::Failed(_)));
+
+    // Remove torrent
+    api.remove(&target, true).await?;
+    assert!(api.get(&target).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_restore_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+    let dir = std::env::temp_dir().join("hightorrent_api_backup_v1");
+
+    // The torrent may already be there from another test
+    if api.get(&target).await?.is_none() {
+        api.add()
+            .torrent_bytes(V1_TORRENT.to_vec())
+            .paused(true)
+            .tags(vec!["backup".to_string()])
+            .send()
+            .await?;
+    }
+
+    let report = backup(&*api, &MultiTarget::Hash(target.clone()), &dir).await?;
+    assert!(report.failures.is_empty());
+    assert_eq!(report.manifest.torrents.len(), 1);
+    assert!(report.manifest.torrents[0].torrent_file.is_some());
+
+    // Remove torrent, then restore it
+    api.remove(&target, false).await?;
+    let report = restore(&*api, &dir, &RestoreOptions::default()).await?;
+    assert_eq!(report.entries.len(), 1);
+    assert!(matches!(report.entries[0].result, RestoreResult::Restored));
+    assert!(report.entries[0].differences.is_empty());
+
+    // Restoring again finds a duplicate
+    let report = restore(&*api, &dir, &RestoreOptions::default()).await?;
+    assert!(matches!(report.entries[0].result, RestoreResult::Duplicate));
+
+    // Remove torrent
+    api.remove(&target, true).await?;
+    assert!(api.get(&target).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn migrate_dry_run() -> Result<(), ApiError> {
+    let api = client().await;
+
+    // A dry run does not touch either backend, so we can migrate onto ourselves, where every
+    // torrent is a duplicate
+    let before = api.list().await?.to_vec().len();
+    let report = migrate(&*api, &*api, &Migration::new().dry_run(true)).await?;
+    assert_eq!(report.entries.len(), before);
+    assert!(
+        report
+            .entries
+            .iter()
+            .all(|entry| matches!(entry.result, MigrationResult::Duplicate))
+    );
+    assert_eq!(api.list().await?.to_vec().len(), before);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn replace_trackers_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    if api.get(&target).await?.is_some() {
+        api.remove(&target, false).await?;
+    }
+    api.add().magnet(V1_MAGNET).paused(true).send().await?;
+    let before = api.get_tracker_details(&target).await?;
+
+    let replacement = |dry_run| {
+        TrackerReplacement::new("tracker.torrent.eu.org", "tracker.example.org")
+            .target(MultiTarget::Hash(target.clone()))
+            .dry_run(dry_run)
+    };
+
+    // A dry run reports the edit without changing anything
+    let report = replace_trackers(&*api, &replacement(true)).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        TrackerReplacementResult::DryRun
+    ));
+    assert_eq!(report.edits(), 1);
+    assert_eq!(
+        report.entries[0].edits[0].to,
+        "udp://tracker.example.org:451"
+    );
+    assert_eq!(api.get_tracker_details(&target).await?, before);
+
+    let report = replace_trackers(&*api, &replacement(false)).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        TrackerReplacementResult::Replaced
+    ));
+    let after = api.get_tracker_details(&target).await?;
+    let tiers = |details: &hightorrent_api::api::TrackerDetails| -> Vec<(String, Option<u32>)> {
+        details
+            .trackers
+            .iter()
+            .map(|t| {
+                (
+                    t.url
+                        .replace("tracker.torrent.eu.org", "tracker.example.org"),
+                    t.tier,
+                )
+            })
+            .collect()
+    };
+    assert_eq!(tiers(&after), tiers(&before));
+
+    // Nothing matches anymore
+    let report = replace_trackers(&*api, &replacement(false)).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        TrackerReplacementResult::Unchanged
+    ));
+
+    // Editing back a single tracker
+    api.edit_tracker(
+        &target,
+        "udp://tracker.example.org:451",
+        "udp://tracker.torrent.eu.org:451",
+    )
+    .await?;
+    assert_eq!(
+        tiers(&api.get_tracker_details(&target).await?),
+        tiers(&before)
+    );
+
+    api.remove(&target, true).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn add_options_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    // The torrent may already be there from another test
+    if api.get(&target).await?.is_some() {
+        api.remove(&target, false).await?;
+    }
+
+    api.add()
+        .torrent_file(Path::new("tests/tails-amd64-5.6.img.torrent"))
+        .paused(true)
+        .category("hightorrent-test")
+        .rename("hightorrent renamed")
+        .content_layout(ContentLayout::Subfolder)
+        .auto_management(false)
+        .upload_limit(10240)
+        .download_limit(20480)
+        .ratio_limit(SeedingLimit::Limit(1.5))
+        .seeding_time_limit(SeedingLimit::Limit(Duration::from_secs(30)))
+        .sequential_download(true)
+        .first_last_piece_priority(true)
+        .send()
+        .await?;
+
+    let torrent = &api
+        ._list_target_raw(&MultiTarget::Hash(target.clone()))
+        .await?[0];
+    assert_eq!(torrent.name, "hightorrent renamed");
+    assert_eq!(torrent.category, "hightorrent-test");
+    assert_eq!(torrent.upload_limit, 10240);
+    assert_eq!(torrent.download_limit, 20480);
+    assert_eq!(torrent.ratio_limit, 1.5);
+    // Rounded up to a minute, instead of 0 (no seeding at all)
+    assert_eq!(torrent.seeding_time_limit, 1);
+    assert!(torrent.sequential_download);
+    assert!(torrent.first_last_piece_priority);
+    assert!(!torrent.auto_management);
+    assert!(matches!(
+        torrent.state.as_str(),
+        "stoppedDL" | "checkingResumeData"
+    ));
+
+    // The single file is in a subfolder
+    let files = api.get_files(&target).await?;
+    assert_eq!(files[0].path.components().count(), 2);
+
+    api.remove(&target, true).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn queue_disabled() -> Result<(), ApiError> {
+    let api = client().await;
+
+    // Queueing is disabled in the test qBittorrent configuration
+    let res = api.top_priority(&MultiTarget::All).await;
+    assert!(matches!(res, Err(ApiError::QueueingDisabled)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dyn_api_v1() -> Result<(), ApiError> {
+    // Hold the lock so other tests don't touch the same torrent
+    let _lock = client().await;
+    let api: Arc<dyn Api> =
+        Arc::login("qbittorrent+http://localhost:8080", "admin", "adminadmin").await?;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    if api.get(&target).await?.is_some() {
+        api.remove(&target, false).await?;
+    }
+
+    let hash = api.add().magnet(V1_MAGNET).paused(true).send().await?;
+    assert_eq!(hash.unwrap().id().as_str(), V1_ID);
+    assert!(api.get(&target).await?.is_some());
+
+    api.remove(&target, true).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn capabilities() -> Result<(), ApiError> {
+    let api = client().await;
+    let capabilities = api.capabilities().await?;
+    let version = api.version().await?;
+
+    // CI tests qBittorrent v4.6 and v5, so capabilities depend on the running release
+    assert_eq!(
+        capabilities.supports_protocol(Protocol::Hybrid),
+        version.app >= QBittorrentVersion::V2_SINCE
+    );
+    assert_eq!(
+        capabilities.supports(Operation::Export),
+        version.webapi >= QBittorrentVersion::EXPORT_SINCE_WEBAPI
+    );
+    assert_eq!(
+        capabilities.supports_add_option(AddOption::StopCondition),
+        version.app >= QBittorrentVersion::STOP_CONDITION_SINCE
+    );
+    assert_eq!(
+        capabilities.supports(Operation::AddTrackerTier),
+        version.app >= QBittorrentVersion::TRACKER_TIERS_SINCE
+    );
+    assert!(capabilities.supports(Operation::EditTracker));
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_expired() -> Result<(), ApiError> {
+    let api = client().await;
+
+    // Expire the session, then make concurrent requests which all log in again
+    api._post(api._endpoint("auth/logout")).await?;
+    let (first, second, third) = tokio::join!(api.list(), api.list(), api.list());
+    first?;
+    second?;
+    third?;
+
+    // A failed login is reported instead of ignored
+    let invalid = QBittorrentClient::new_not_logged_in("http://localhost:8080", "admin", "wrong")?;
+    assert!(matches!(
+        invalid.reconnect().await,
+        Err(ApiError::InvalidLogin { .. })
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_store() -> Result<(), ApiError> {
+    let _lock = client().await;
+    let path = std::env::temp_dir().join("hightorrent_api_session");
+    let store = FileSessionStore::new(&path);
+
+    // An expired session is replaced by a new login
+    store.save("expired").await?;
+    let api = QBittorrentClient::new_not_logged_in("http://localhost:8080", "admin", "adminadmin")?
+        .session_store(store.clone());
+    api.do_login().await?;
+    let sid = store.load().await?.unwrap();
+    assert_ne!(sid, "expired");
+
+    // A valid session is reused without logging in again
+    let api = QBittorrentClient::new_not_logged_in("http://localhost:8080", "admin", "adminadmin")?
+        .session_store(store.clone());
+    api.do_login().await?;
+    api.list().await?;
+    assert_eq!(store.load().await?, Some(sid));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_flags_v1() -> Result<(), ApiError> {
+    let api = client().await;
+    let v1 = SingleTarget::new(V1_V1HASH).unwrap();
+    let v2 = SingleTarget::new(V2_V2HASH).unwrap();
+
+    // The torrents may already be there from another test
+    for target in [&v1, &v2] {
+        if api.get(target).await?.is_some() {
+            api.remove(target, false).await?;
+        }
+    }
+    for magnet in [V1_MAGNET, V2_MAGNET] {
+        api.add().magnet(magnet).paused(true).send().await?;
+    }
+
+    let flags = async |flag| -> Result<(bool, bool), ApiError> {
+        let v1 = &api._list_target_raw(&MultiTarget::Hash(v1.clone())).await?[0];
+        let v2 = &api._list_target_raw(&MultiTarget::Hash(v2.clone())).await?[0];
+        Ok((get_flag(v1, flag), get_flag(v2, flag)))
+    };
+
+    for (flag, (field, _)) in FLAGS.iter().enumerate() {
+        // Only the v1 torrent does not have the desired state
+        set_flag(&api, flag, &MultiTarget::All, false).await?;
+        set_flag(&api, flag, &MultiTarget::Hash(v2.clone()), true).await?;
+        assert_eq!(flags(flag).await?, (false, true));
+
+        // Setting the same value twice does not toggle the flag back
+        for _ in 0..2 {
+            set_flag(&api, flag, &MultiTarget::All, true).await?;
+            assert_eq!(flags(flag).await?, (true, true), "{field}");
+        }
+        for _ in 0..2 {
+            set_flag(&api, flag, &MultiTarget::Hash(v1.clone()), false).await?;
+            assert_eq!(flags(flag).await?, (false, true), "{field}");
+        }
+
+        set_flag(&api, flag, &MultiTarget::All, false).await?;
+    }
+
+    for target in [&v1, &v2] {
+        api.remove(target, true).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,977 @@
+//! Tests against a fake qBittorrent daemon, which run without a qBittorrent instance.
+
+use hightorrent::{MultiTarget, SingleTarget};
+use hightorrent_api::api::{
+    AddOption, AddSource, Backend, BatchAddResult, ContentLayout, Operation, Protocol,
+    SeedingLimit, StopCondition, TrackerStatus,
+};
+use hightorrent_api::backup::{RestoreOptions, RestoreResult, backup, restore};
+use hightorrent_api::migrate::{Migration, MigrationResult, migrate};
+use hightorrent_api::qbittorrent::{
+    Auth, FileSessionStore, QBittorrentVersion, SessionStore, Version, normalize_state,
+};
+use hightorrent_api::retry::RetryPolicy;
+use hightorrent_api::trackers::TrackerReplacement;
+use hightorrent_api::{Api, ApiError, QBittorrentClient};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+mod common;
+use common::*;
+
+#[tokio::test]
+async fn batch_failures() -> Result<(), ApiError> {
+    // The added torrent only appears in the list after a while
+    let listed = std::sync::atomic::AtomicUsize::new(0);
+    let (host, requests) = fake_server(move |request| {
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        match path.split('?').next().unwrap_or_default() {
+            "/api/v2/app/version" => ("200 OK", "v5.1.2".to_string()),
+            "/api/v2/app/webapiversion" => ("200 OK", "2.11.4".to_string()),
+            "/api/v2/torrents/info" => {
+                let torrents = match listed.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0..3 => serde_json::json!([]),
+                    _ => serde_json::json!([fake_torrent(V1_V1HASH)]),
+                };
+                ("200 OK", torrents.to_string())
+            }
+            "/api/v2/torrents/add" if request.contains("broken") => {
+                ("500 Internal Server Error", String::new())
+            }
+            "/api/v2/torrents/add" => ("200 OK", "Ok.".to_string()),
+            _ => ("200 OK", String::new()),
+        }
+    });
+    let api = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+
+    // Invalid torrents don't prevent the others from being added
+    let results = api
+        .add()
+        .batch(vec![
+            AddSource::magnet("magnet:?dn=nohash"),
+            AddSource::magnet(V1_MAGNET),
+            AddSource::torrent_bytes(b"not a torrent".to_vec()),
+        ])
+        .send()
+        .await?;
+    assert!(
+        matches!(&results[0], BatchAddResult::Failed(e) if matches!(**e, ApiError::InvalidMagnet { .. }))
+    );
+    assert!(matches!(&results[1], BatchAddResult::Added(Some(hash)) if hash.as_str() == V1_V1HASH));
+    assert!(
+        matches!(&results[2], BatchAddResult::Failed(e) if matches!(**e, ApiError::InvalidTorrent { .. }))
+    );
+
+    // A failed request is reported for each of its torrents
+    let results = api
+        .add()
+        .category("broken")
+        .batch(vec![
+            AddSource::magnet("magnet:?xt=urn:btih:bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"),
+            AddSource::url("https://example.com/tails.torrent"),
+        ])
+        .send()
+        .await?;
+    let (BatchAddResult::Failed(first), BatchAddResult::Failed(second)) =
+        (&results[0], &results[1])
+    else {
+        panic!("unexpected results {results:?}");
+    };
+    assert!(matches!(**first, ApiError::Server { status: 500, .. }));
+    assert!(Arc::ptr_eq(first, second));
+    assert_eq!(
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.contains("/torrents/add"))
+            .count(),
+        2
+    );
+
+    // Accepted torrents missing from the list after the check are not reported as rejected
+    let results = api
+        .clone()
+        .batch_check_timeout(Duration::ZERO)
+        .add()
+        .batch(vec![AddSource::magnet(
+            "magnet:?xt=urn:btih:cccccccccccccccccccccccccccccccccccccccc",
+        )])
+        .send()
+        .await?;
+    assert!(
+        matches!(&results[0], BatchAddResult::NotYetVisible(hash) if hash.as_str() == "cccccccccccccccccccccccccccccccccccccccc")
+    );
+
+    // Invalid options are reported without sending anything
+    let results = api
+        .add()
+        .ratio_limit(SeedingLimit::Limit(-1.0))
+        .batch(vec![AddSource::magnet(V1_MAGNET)])
+        .send()
+        .await?;
+    assert!(
+        matches!(&results[0], BatchAddResult::Failed(e) if matches!(**e, ApiError::InvalidAddOption { .. }))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_offline() -> Result<(), ApiError> {
+    let broken = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    let (host, _) = fake_server(move |request| {
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        match path.split('?').next().unwrap_or_default() {
+            "/api/v2/app/version" => ("200 OK", "v5.1.2".to_string()),
+            "/api/v2/app/webapiversion" => ("200 OK", "2.11.4".to_string()),
+            "/api/v2/torrents/info" => (
+                "200 OK",
+                serde_json::json!([fake_torrent(V1_ID), fake_torrent(broken)]).to_string(),
+            ),
+            "/api/v2/torrents/trackers" if path.contains(broken) => {
+                ("500 Internal Server Error", String::new())
+            }
+            "/api/v2/torrents/trackers" => (
+                "200 OK",
+                r#"[
+                    {"url":"** [DHT] **","status":2,"msg":"","tier":-1,"num_peers":0,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+                    {"url":"udp://backup.example:1337/announce","status":1,"msg":"","tier":1,"num_peers":-1,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+                    {"url":"https://tracker.example/announce","status":2,"msg":"","tier":0,"num_peers":0,"num_seeds":0,"num_leeches":0,"num_downloaded":0}
+                ]"#
+                .to_string(),
+            ),
+            "/api/v2/torrents/export" => ("409 Conflict", String::new()),
+            _ => ("200 OK", String::new()),
+        }
+    });
+    let source = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+    let dir = std::env::temp_dir().join("hightorrent_api_backup_offline");
+
+    // A torrent failing to back up does not stop the others
+    let report = backup(&source, &MultiTarget::All, &dir).await?;
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].hash.as_str(), broken);
+    assert!(matches!(
+        report.failures[0].error,
+        ApiError::Server { status: 500, .. }
+    ));
+    assert_eq!(report.manifest.torrents.len(), 1);
+    let tiers: Vec<(&str, Option<u32>)> = report.manifest.torrents[0]
+        .trackers
+        .iter()
+        .map(|tracker| (tracker.url.as_str(), tracker.tier))
+        .collect();
+    assert_eq!(
+        tiers,
+        [
+            ("https://tracker.example/announce", Some(0)),
+            ("udp://backup.example:1337/announce", Some(1))
+        ]
+    );
+
+    // Trackers are restored in their tier, once the torrent appears in the backend
+    let (host, requests) =
+        fake_qbittorrent("v5.1.2", "2.11.4", serde_json::json!([fake_torrent(V1_ID)]));
+    let destination = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+    let report = restore(&destination, &dir, &RestoreOptions::default()).await?;
+    assert!(matches!(report.entries[0].result, RestoreResult::Restored));
+    let added: Vec<String> = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|request| request.contains("/torrents/addtrackers"))
+        .map(|request| request.lines().next().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(added.len(), 2);
+    assert!(added[0].contains("urls=https%3a%2f%2ftracker.example%2fannounce"));
+    assert!(added[1].contains("urls=%0audp%3a%2f%2fbackup.example%3a1337%2fannounce"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn migrate_offline() -> Result<(), ApiError> {
+    let other = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    let (host, requests) = fake_qbittorrent(
+        "v5.1.2",
+        "2.11.4",
+        serde_json::json!([fake_torrent(V1_ID), fake_torrent(other)]),
+    );
+    let source = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+    let (host, _) = fake_qbittorrent("v5.1.2", "2.11.4", serde_json::json!([fake_torrent(V1_ID)]));
+    let destination = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+
+    // A dry run reports the torrents already on the destination
+    let report = migrate(&source, &destination, &Migration::new().dry_run(true)).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        MigrationResult::Duplicate
+    ));
+    assert!(matches!(report.entries[1].result, MigrationResult::DryRun));
+    assert!(
+        !requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|request| request.starts_with("post"))
+    );
+
+    // Failing to write the progress log does not stop the migration
+    let migration = Migration::new()
+        .target(MultiTarget::Hash(SingleTarget::new(other).unwrap()))
+        .progress_log(Path::new("/nonexistent/hightorrent/migration.log"));
+    let report = migrate(&source, &source, &migration).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        MigrationResult::Migrated
+    ));
+    assert!(matches!(
+        report.entries[0].progress_log_error,
+        Some(ApiError::MigrationLog { .. })
+    ));
+    assert!(report.entries[0].differences.is_empty());
+
+    // A torrent is logged once added, even when its trackers can't be added afterwards
+    let log = std::env::temp_dir().join("hightorrent_api_migration_resume.log");
+    let _ = std::fs::remove_file(&log);
+    let (host, _) = fake_server(move |request| {
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        match path.split('?').next().unwrap_or_default() {
+            "/api/v2/app/version" => ("200 OK", "v5.1.2".to_string()),
+            "/api/v2/app/webapiversion" => ("200 OK", "2.11.4".to_string()),
+            "/api/v2/torrents/info" => (
+                "200 OK",
+                serde_json::json!([fake_torrent(other)]).to_string(),
+            ),
+            "/api/v2/torrents/trackers" => ("500 Internal Server Error", String::new()),
+            _ => ("200 OK", "Ok.".to_string()),
+        }
+    });
+    let broken = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+    let migration = Migration::new()
+        .target(MultiTarget::Hash(SingleTarget::new(other).unwrap()))
+        .remove_source(true)
+        .progress_log(&log);
+    let report = migrate(&source, &broken, &migration).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        MigrationResult::Failed(ApiError::Server { status: 500, .. })
+    ));
+    assert!(!report.entries[0].removed_from_source);
+
+    // Resuming verifies the torrent and removes it from the source, instead of adding it again
+    requests.lock().unwrap().clear();
+    let report = migrate(&source, &source, &migration).await?;
+    assert!(matches!(
+        report.entries[0].result,
+        MigrationResult::AlreadyMigrated
+    ));
+    assert!(report.entries[0].removed_from_source);
+    let sent: Vec<String> = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|request| request.contains("/torrents/add") || request.contains("/torrents/delete"))
+        .map(|request| request.lines().next().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].contains("/torrents/delete"));
+
+    // Removed torrents are then left alone
+    requests.lock().unwrap().clear();
+    let report = migrate(&source, &source, &migration).await?;
+    assert!(report.entries[0].removed_from_source);
+    assert!(
+        !requests.lock().unwrap().iter().any(
+            |request| request.contains("/torrents/add") || request.contains("/torrents/delete")
+        )
+    );
+
+    // Settings are compared field by field, also onto a backend chosen at runtime
+    let mut categorized = fake_torrent(other);
+    categorized["category"] = "linux".into();
+    categorized["ratio_limit"] = 1.5.into();
+    let (host, _) = fake_qbittorrent("v5.1.2", "2.11.4", serde_json::json!([categorized]));
+    let destination: Box<dyn Api> = Box::new(QBittorrentClient::new_not_logged_in(&host, "", "")?);
+    let migration = Migration::new().target(MultiTarget::Hash(SingleTarget::new(other).unwrap()));
+    let report = migrate(&source, &*destination, &migration).await?;
+    let fields: Vec<(&str, &str, &str)> = report.entries[0]
+        .differences
+        .iter()
+        .map(|difference| {
+            (
+                difference.field,
+                difference.expected.as_str(),
+                difference.actual.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        fields,
+        [("category", "", "linux"), ("ratio_limit", "", "1.5")]
+    );
+    Ok(())
+}
+
+#[test]
+fn tracker_rewrite() {
+    let replacement = TrackerReplacement::new("old.example", "new.example");
+    assert_eq!(
+        replacement.rewrite("https://tracker.old.example/announce"),
+        Some("https://tracker.new.example/announce".to_string())
+    );
+    assert_eq!(replacement.rewrite("udp://other.example:1337"), None);
+
+    // An empty pattern matches nothing, and a rewrite to the same URL is not an edit
+    assert_eq!(TrackerReplacement::new("", "x").rewrite("udp://a:1"), None);
+    let https = TrackerReplacement::with(|url| Some(url.replacen("http://", "https://", 1)));
+    assert_eq!(https.rewrite("https://a/announce"), None);
+    assert_eq!(
+        https.rewrite("http://a/announce"),
+        Some("https://a/announce".to_string())
+    );
+}
+
+#[tokio::test]
+async fn add_options_fields() -> Result<(), ApiError> {
+    let (host, requests) = fake_qbittorrent("v5.1.2", "2.11.4", serde_json::json!([]));
+    let api = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+    let add_requests = || {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.contains("/torrents/add"))
+            .cloned()
+            .collect::<Vec<String>>()
+    };
+
+    api.add()
+        .magnet(V1_MAGNET)
+        .paused(true)
+        .category("linux")
+        .rename("tails")
+        .skip_checking(true)
+        .content_layout(ContentLayout::NoSubfolder)
+        .stop_condition(StopCondition::MetadataReceived)
+        .auto_management(false)
+        .download_path("/incomplete")
+        .upload_limit(1000)
+        .download_limit(2000)
+        .ratio_limit(SeedingLimit::Limit(1.5))
+        .seeding_time_limit(SeedingLimit::Limit(Duration::from_secs(61)))
+        .sequential_download(true)
+        .first_last_piece_priority(true)
+        .add_to_top_of_queue(true)
+        .send()
+        .await?;
+
+    let request = &add_requests()[0];
+    for (field, value) in [
+        ("stopped", "true"),
+        ("category", "linux"),
+        ("rename", "tails"),
+        ("skip_checking", "true"),
+        ("contentlayout", "nosubfolder"),
+        ("stopcondition", "metadatareceived"),
+        ("autotmm", "false"),
+        ("usedownloadpath", "true"),
+        ("downloadpath", "/incomplete"),
+        ("uplimit", "1000"),
+        ("dllimit", "2000"),
+        ("ratiolimit", "1.5"),
+        // Rounded up to the next minute
+        ("seedingtimelimit", "2"),
+        ("sequentialdownload", "true"),
+        ("firstlastpieceprio", "true"),
+        ("addtotopofqueue", "true"),
+    ] {
+        assert_eq!(
+            form_field(request, field).as_deref(),
+            Some(value),
+            "{field}"
+        );
+    }
+
+    // Whole minutes are sent as is, and less than a minute is not "no seeding"
+    for (limit, minutes) in [(120, "2"), (1, "1"), (0, "0")] {
+        api.add()
+            .magnet(V1_MAGNET)
+            .seeding_time_limit(SeedingLimit::Limit(Duration::from_secs(limit)))
+            .send()
+            .await?;
+        let request = add_requests().pop().unwrap();
+        assert_eq!(
+            form_field(&request, "seedingtimelimit").as_deref(),
+            Some(minutes)
+        );
+    }
+
+    // The global limit and unlimited have their own values
+    for (ratio, time, value) in [
+        (SeedingLimit::Global, SeedingLimit::Global, "-2"),
+        (SeedingLimit::Unlimited, SeedingLimit::Unlimited, "-1"),
+    ] {
+        api.add()
+            .magnet(V1_MAGNET)
+            .ratio_limit(ratio)
+            .seeding_time_limit(time)
+            .send()
+            .await?;
+        let request = add_requests().pop().unwrap();
+        assert_eq!(form_field(&request, "ratiolimit").as_deref(), Some(value));
+        assert_eq!(
+            form_field(&request, "seedingtimelimit").as_deref(),
+            Some(value)
+        );
+    }
+
+    // Invalid ratio limits are refused before sending anything
+    let sent = add_requests().len();
+    for ratio in [f64::NAN, f64::INFINITY, -1.0] {
+        let res = api
+            .add()
+            .magnet(V1_MAGNET)
+            .ratio_limit(SeedingLimit::Limit(ratio))
+            .send()
+            .await;
+        let Err(e) = res else {
+            panic!("Invalid ratio limit {ratio} was accepted");
+        };
+        assert!(matches!(e, ApiError::InvalidAddOption { .. }));
+        assert_eq!(e.backend_message(), None);
+    }
+    assert_eq!(add_requests().len(), sent);
+
+    // Torrents from URLs can't be waited for, as their infohash is unknown
+    for wait in [
+        api.add().wait(Duration::from_secs(1)),
+        api.add().wait_metadata(Duration::from_secs(1)),
+    ] {
+        let res = wait.url("https://example.com/tails.torrent").send().await;
+        assert!(matches!(res, Err(ApiError::UnsupportedWait { .. })));
+    }
+    assert_eq!(add_requests().len(), sent);
+    Ok(())
+}
+
+#[test]
+fn backend_url() -> Result<(), ApiError> {
+    assert_eq!(
+        Backend::from_url("qbittorrent+https://seedbox.lan/qbt")?,
+        (Backend::QBittorrent, "https://seedbox.lan/qbt".to_string())
+    );
+    assert_eq!(
+        Backend::from_url("qbittorrent://localhost:8080")?,
+        (Backend::QBittorrent, "http://localhost:8080".to_string())
+    );
+    assert_eq!(
+        Backend::from_url("http://localhost:8080")?,
+        (Backend::QBittorrent, "http://localhost:8080".to_string())
+    );
+    assert!(matches!(
+        Backend::from_url("transmission://localhost:9091"),
+        Err(ApiError::UnsupportedBackend { .. })
+    ));
+    assert!(matches!(
+        Backend::from_url("qbittorrent+ftp://localhost"),
+        Err(ApiError::UnsupportedBackend { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn qbittorrent_version() -> Result<(), ApiError> {
+    let v4_3 = QBittorrentVersion {
+        app: "v4.3.9".parse()?,
+        webapi: "2.8.2".parse()?,
+    };
+    let capabilities = v4_3.capabilities();
+    assert!(capabilities.supports_protocol(Protocol::V1));
+    assert!(!capabilities.supports_protocol(Protocol::Hybrid));
+    assert!(!capabilities.supports(Operation::Export));
+    assert!(!capabilities.supports(Operation::AddTrackerTier));
+    assert!(!capabilities.supports_add_option(AddOption::StopCondition));
+    assert!(capabilities.supports_add_option(AddOption::ContentLayout));
+
+    assert_eq!(v4_3.stopped_field(), "paused");
+    assert_eq!(v4_3.stop_endpoint(), "torrents/pause");
+
+    let v5_1 = QBittorrentVersion {
+        app: "v5.1.2".parse()?,
+        webapi: "2.11.4".parse()?,
+    };
+    assert_eq!(v5_1.stopped_field(), "stopped");
+    assert_eq!(v5_1.start_endpoint(), "torrents/start");
+
+    assert_eq!(normalize_state("pausedDL"), "stoppedDL");
+    assert_eq!(normalize_state("stoppedUP"), "stoppedUP");
+    assert_eq!(normalize_state("metaDL"), "metaDL");
+
+    assert_eq!("v5.1.0beta1".parse::<Version>()?, Version::new(5, 1, 0));
+    assert_eq!("2.11".parse::<Version>()?, Version::new(2, 11, 0));
+    assert!("Forbidden".parse::<Version>().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn capabilities_negotiated() -> Result<(), ApiError> {
+    let (host, requests) = fake_qbittorrent("v4.3.9", "2.8.2", serde_json::json!([]));
+    let api = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+
+    // The version is only asked once, then reused
+    for _ in 0..2 {
+        let capabilities = api.capabilities().await?;
+        assert!(!capabilities.supports(Operation::Export));
+        assert!(capabilities.supports_add_option(AddOption::ContentLayout));
+    }
+    let version_requests = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|request| request.contains("/app/"))
+        .count();
+    assert_eq!(version_requests, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_policy() -> Result<(), ApiError> {
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = attempts.clone();
+    let policy = RetryPolicy::new()
+        .max_attempts(3)
+        .initial_backoff(Duration::from_millis(1))
+        .on_attempt(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+    // Nothing listens on this port, so every request fails with a network error
+    let api = QBittorrentClient::new_not_logged_in("http://127.0.0.1:9", "admin", "adminadmin")?
+        .retry_policy(policy.clone());
+
+    // Idempotent operations are retried
+    assert!(matches!(api.list().await, Err(ApiError::Http { .. })));
+    assert_eq!(attempts.swap(0, Ordering::SeqCst), 3);
+
+    // Non-idempotent operations are not, unless enabled
+    assert!(api.increase_priority(&MultiTarget::All).await.is_err());
+    assert_eq!(attempts.swap(0, Ordering::SeqCst), 1);
+
+    let api = api.retry_policy(policy.clone().retry_non_idempotent(true));
+    assert!(api.increase_priority(&MultiTarget::All).await.is_err());
+    assert_eq!(attempts.swap(0, Ordering::SeqCst), 3);
+
+    // Other errors are not retried
+    let result: Result<(), ApiError> = policy
+        .run(true, || async { Err(ApiError::RejectedTorrent) })
+        .await;
+    assert!(matches!(result, Err(ApiError::RejectedTorrent)));
+    assert_eq!(attempts.swap(0, Ordering::SeqCst), 1);
+
+    // Backoff doubles until the maximum, and jitter keeps at least half of it
+    let policy = RetryPolicy::new()
+        .initial_backoff(Duration::from_secs(1))
+        .max_backoff(Duration::from_secs(3));
+    for _ in 0..10 {
+        let delay = policy.backoff(2);
+        assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+    }
+    let policy = policy.jitter(false);
+    assert_eq!(policy.backoff(1), Duration::from_secs(1));
+    assert_eq!(policy.backoff(2), Duration::from_secs(2));
+    assert_eq!(policy.backoff(5), Duration::from_secs(3));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn client_builder() -> Result<(), ApiError> {
+    // A daemon which answers after the client gives up
+    let (host, requests) = fake_server(|_| {
+        std::thread::sleep(Duration::from_secs(1));
+        ("200 OK", String::new())
+    });
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("x-hightorrent-test", "1".parse().unwrap());
+    let api = QBittorrentClient::builder(&host, "admin", "adminadmin")
+        .timeout(Duration::from_millis(200))
+        .connect_timeout(Duration::from_millis(200))
+        .pool_max_idle_per_host(1)
+        .user_agent("hightorrent-test")
+        .default_headers(headers)
+        .build()?;
+
+    // The request times out instead of hanging forever
+    assert!(matches!(api.list().await, Err(ApiError::Timeout { .. })));
+    let request = requests.lock().unwrap()[0].clone();
+    assert!(request.contains("user-agent: hightorrent-test"));
+    assert!(request.contains("x-hightorrent-test: 1"));
+
+    // Invalid proxies are reported when building the client
+    assert!(matches!(
+        QBittorrentClient::builder(&host, "admin", "adminadmin")
+            .proxy("not a proxy")
+            .build(),
+        Err(ApiError::ClientInit { .. })
+    ));
+    QBittorrentClient::builder(&host, "admin", "adminadmin")
+        .proxy("socks5://localhost:1080")
+        .build()?;
+
+    Ok(())
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn client_tls() -> Result<(), ApiError> {
+    let builder = || QBittorrentClient::builder("https://localhost:8080", "admin", "adminadmin");
+
+    builder()
+        .certificate_fingerprint(
+            "3A:1F:00:11:22:33:44:55:66:77:88:99:AA:BB:CC:DD:EE:FF:00:11:22:33:44:55:66:77:88:99:AA:BB:CC:9C",
+        )
+        .build()?;
+    builder()
+        .certificate_fingerprint("3a1f00112233445566778899aabbccddeeff00112233445566778899aabbcc9c")
+        .build()?;
+
+    for invalid in [
+        "3A:1F",
+        "zz1f00112233445566778899aabbccddeeff00112233445566778899aabbcc9c",
+    ] {
+        assert!(matches!(
+            builder().certificate_fingerprint(invalid).build(),
+            Err(ApiError::ClientInit { .. })
+        ));
+    }
+    assert!(matches!(
+        builder().add_root_certificate(b"not a certificate").build(),
+        Err(ApiError::ClientInit { .. })
+    ));
+    assert!(matches!(
+        builder()
+            .client_certificate(b"not a certificate", b"not a key")
+            .build(),
+        Err(ApiError::ClientInit { .. })
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn auth_modes() -> Result<(), ApiError> {
+    // Authentication bypassed: no login, no credentials
+    let (host, requests) = fake_server(|_| ("200 OK", "2.11.2".to_string()));
+    let api = QBittorrentClient::builder(&host, "", "")
+        .auth(Auth::Bypass)
+        .login()
+        .await?;
+    api.reconnect().await?;
+    assert_eq!(
+        api.negotiated_version().await?.webapi,
+        Version::new(2, 11, 2)
+    );
+    for request in requests.lock().unwrap().iter() {
+        assert!(!request.contains("auth/login"));
+        assert!(!request.contains("authorization:"));
+    }
+
+    // API key sent with every request
+    let (host, requests) = fake_server(|_| ("200 OK", "2.11.2".to_string()));
+    QBittorrentClient::builder(&host, "", "")
+        .auth(Auth::ApiKey("qbt_key".to_string()))
+        .login()
+        .await?;
+    let requests = requests.lock().unwrap().clone();
+    assert!(!requests.is_empty());
+    for request in requests.iter() {
+        assert!(request.contains("authorization: bearer qbt_key"));
+    }
+
+    // Reverse proxy credentials sent alongside the login
+    let (host, requests) = fake_server(|_| ("200 OK", "Fails.".to_string()));
+    let result = QBittorrentClient::builder(&host, "admin", "adminadmin")
+        .basic_auth("proxy", Some("secret"))
+        .login()
+        .await;
+    assert!(matches!(result, Err(ApiError::InvalidLogin { .. })));
+    let requests = requests.lock().unwrap().clone();
+    assert!(requests[0].contains("auth/login"));
+    // proxy:secret
+    assert!(requests[0].contains("authorization: basic chjvehk6c2vjcmv0"));
+
+    // A stored session is sent from the cookie store, along with the cookies of a reverse proxy
+    let (host, requests) = fake_server(|_| {
+        (
+            "200 OK\r\nSet-Cookie: proxy=sso; Path=/",
+            "2.11.2".to_string(),
+        )
+    });
+    let path = std::env::temp_dir().join("hightorrent_api_session_cookie");
+    let store = FileSessionStore::new(&path);
+    store.save("stored").await?;
+    QBittorrentClient::builder(&host, "admin", "adminadmin")
+        .session_store(store)
+        .login()
+        .await?;
+    let requests = requests.lock().unwrap().clone();
+    assert!(requests.len() > 1);
+    assert!(
+        requests
+            .iter()
+            .all(|request| !request.contains("auth/login"))
+    );
+    let cookies: Vec<&str> = requests[1]
+        .lines()
+        .filter(|line| line.starts_with("cookie:"))
+        .collect();
+    assert_eq!(cookies.len(), 1);
+    assert!(cookies[0].contains("sid=stored"));
+    assert!(cookies[0].contains("proxy=sso"));
+
+    // Bypass refused by the daemon
+    let (host, _) = fake_server(|_| ("403 Forbidden", "Forbidden".to_string()));
+    let result = QBittorrentClient::builder(&host, "", "")
+        .auth(Auth::Bypass)
+        .bearer_auth("sso-token")
+        .login()
+        .await;
+    assert!(matches!(result, Err(ApiError::InvalidLogin { .. })));
+
+    // Both need the Authorization header
+    assert!(matches!(
+        QBittorrentClient::builder(&host, "", "")
+            .auth(Auth::ApiKey("qbt_key".to_string()))
+            .bearer_auth("sso-token")
+            .build(),
+        Err(ApiError::ClientInit { .. })
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn status_errors() -> Result<(), ApiError> {
+    let client = |host: &str| {
+        QBittorrentClient::builder(host, "admin", "adminadmin")
+            .auth(Auth::Bypass)
+            .build()
+    };
+
+    let (host, _) = fake_server(|_| ("400 Bad Request", "Invalid hash".to_string()));
+    let error = client(&host)?.webapi_version().await.unwrap_err();
+    assert!(matches!(error, ApiError::InvalidArgument { .. }));
+    assert_eq!(error.backend_message(), Some("Invalid hash"));
+    assert!(!error.is_retryable());
+
+    let (host, _) = fake_server(|_| ("404 Not Found", String::new()));
+    assert!(matches!(
+        client(&host)?.webapi_version().await,
+        Err(ApiError::UnsupportedOperation { endpoint, .. }) if endpoint == "/api/v2/app/webapiVersion"
+    ));
+
+    let (host, _) = fake_server(|_| ("409 Conflict", "Already exists".to_string()));
+    assert!(matches!(
+        client(&host)?.webapi_version().await,
+        Err(ApiError::Conflict { message }) if message == "Already exists"
+    ));
+
+    // Server errors are retried
+    let (host, requests) = fake_server(|_| ("503 Service Unavailable", "Restarting".to_string()));
+    let error = client(&host)?
+        .retry_policy(
+            RetryPolicy::new()
+                .max_attempts(2)
+                .initial_backoff(Duration::from_millis(1)),
+        )
+        .webapi_version()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ApiError::Server { status: 503, .. }));
+    assert!(error.is_retryable());
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // Also for non-idempotent operations, when enabled
+    let (host, requests) = fake_server(|_| ("503 Service Unavailable", "Restarting".to_string()));
+    let policy = RetryPolicy::new()
+        .max_attempts(2)
+        .initial_backoff(Duration::from_millis(1));
+    let api = client(&host)?.retry_policy(policy.clone());
+    assert!(matches!(
+        api.increase_priority(&MultiTarget::All).await,
+        Err(ApiError::Server { status: 503, .. })
+    ));
+    assert_eq!(requests.lock().unwrap().len(), 1);
+    let api = api.retry_policy(policy.retry_non_idempotent(true));
+    assert!(matches!(
+        api.increase_priority(&MultiTarget::All).await,
+        Err(ApiError::Server { status: 503, .. })
+    ));
+    assert_eq!(requests.lock().unwrap().len(), 3);
+
+    // Logging in from a banned IP address
+    let (host, _) = fake_server(|_| {
+        (
+            "403 Forbidden",
+            "Your IP address has been banned after too many failed authentication attempts."
+                .to_string(),
+        )
+    });
+    let api = QBittorrentClient::new_not_logged_in(&host, "admin", "adminadmin")?;
+    assert!(matches!(
+        api.reconnect().await,
+        Err(ApiError::Banned { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn host_validation() -> Result<(), ApiError> {
+    for host in [
+        "localhost:8080",
+        "ftp://localhost",
+        "http://",
+        "not a url",
+        "",
+    ] {
+        assert!(matches!(
+            QBittorrentClient::new_not_logged_in(host, "admin", "adminadmin"),
+            Err(ApiError::InvalidHost { .. })
+        ));
+    }
+
+    // Behind a reverse proxy, under a path
+    for host in [
+        "https://example.org/qbittorrent",
+        "https://example.org/qbittorrent/",
+    ] {
+        let api = QBittorrentClient::new_not_logged_in(host, "admin", "adminadmin")?;
+        assert_eq!(
+            api._endpoint("torrents/info").as_str(),
+            "https://example.org/qbittorrent/api/v2/torrents/info"
+        );
+        assert_eq!(
+            api._endpoint_params("torrents/trackers", [("hash", "a&b")])
+                .as_str(),
+            "https://example.org/qbittorrent/api/v2/torrents/trackers?hash=a%26b"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn tracker_details() -> Result<(), ApiError> {
+    let (host, requests) = fake_server(|_| {
+        ("200 OK", r#"[
+            {"url":"** [DHT] **","status":2,"msg":"","tier":-1,"num_peers":12,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+            {"url":"** [PeX] **","status":2,"msg":"","tier":-1,"num_peers":3,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+            {"url":"** [LSD] **","status":0,"msg":"","tier":-1,"num_peers":0,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+            {"url":"udp://notier.example:1337/announce","status":1,"msg":"","tier":-1,"num_peers":-1,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+            {"url":"udp://backup.example:1337/announce","status":1,"msg":"","tier":1,"num_peers":-1,"num_seeds":-1,"num_leeches":-1,"num_downloaded":-1},
+            {"url":"https://tracker.example/announce","status":2,"msg":"Welcome","tier":0,"num_peers":5,"num_seeds":10,"num_leeches":5,"num_downloaded":100}
+        ]"#.to_string())
+    });
+    let api = QBittorrentClient::builder(&host, "", "")
+        .auth(Auth::Bypass)
+        .build()?;
+    let target = SingleTarget::new(V1_V1HASH).unwrap();
+
+    let details = api.get_tracker_details(&target).await?;
+    assert!(requests.lock().unwrap()[0].contains("/api/v2/torrents/trackers?hash="));
+    let urls: Vec<&str> = details.trackers.iter().map(|t| t.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        [
+            "https://tracker.example/announce",
+            "udp://backup.example:1337/announce",
+            "udp://notier.example:1337/announce"
+        ]
+    );
+    assert_eq!(details.trackers[0].status, TrackerStatus::Working);
+    assert_eq!(details.trackers[0].seeds, Some(10));
+    assert_eq!(details.trackers[0].message, "Welcome");
+    assert_eq!(details.trackers[1].status, TrackerStatus::NotContacted);
+    assert_eq!(details.trackers[1].peers, None);
+    assert_eq!(details.trackers[2].tier, None);
+    assert_eq!(details.dht.unwrap().peers, Some(12));
+    assert_eq!(details.pex.unwrap().peers, Some(3));
+    assert_eq!(details.lsd.unwrap().status, TrackerStatus::Disabled);
+
+    // Peer sources are not trackers
+    assert_eq!(api.get_trackers(&target).await?.len(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_flags_idempotent() -> Result<(), ApiError> {
+    let other = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+    for (flag, (field, endpoint)) in FLAGS.iter().enumerate() {
+        // Only the v1 torrent does not have the desired state
+        let state = Arc::new(std::sync::Mutex::new(BTreeMap::from([
+            (V1_ID.to_string(), false),
+            (other.to_string(), true),
+        ])));
+        let torrents = state.clone();
+        let (host, requests) = fake_server(move |request| {
+            let mut torrents = torrents.lock().unwrap();
+            if request.contains("/torrents/info") {
+                let list: Vec<serde_json::Value> = torrents
+                    .iter()
+                    .map(|(id, enabled)| {
+                        let mut torrent = fake_torrent(id);
+                        torrent[field] = (*enabled).into();
+                        torrent
+                    })
+                    .collect();
+                return ("200 OK", serde_json::to_string(&list).unwrap());
+            }
+
+            // Toggle endpoints have no value field
+            let value = form_field(request, "value").or_else(|| form_field(request, "enable"));
+            if let Some(hashes) = form_field(request, "hashes") {
+                for id in hashes.split('|') {
+                    let enabled = torrents.get_mut(id).unwrap();
+                    *enabled = value.as_deref().map_or(!*enabled, |value| value == "true");
+                }
+            }
+            ("200 OK", String::new())
+        });
+        let api = QBittorrentClient::new_not_logged_in(&host, "", "")?;
+        let sent = |requests: &Requests| {
+            requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|request| request.contains(endpoint))
+                .filter_map(|request| form_field(request, "hashes"))
+                .collect::<Vec<String>>()
+        };
+
+        // Only the mismatched torrent is sent, and only once
+        for _ in 0..2 {
+            set_flag(&api, flag, &MultiTarget::All, true).await?;
+        }
+        assert_eq!(sent(&requests), [V1_ID]);
+        assert!(state.lock().unwrap().values().all(|enabled| *enabled));
+
+        for _ in 0..2 {
+            set_flag(
+                &api,
+                flag,
+                &MultiTarget::Hash(SingleTarget::new(V1_V1HASH).unwrap()),
+                false,
+            )
+            .await?;
+        }
+        assert_eq!(sent(&requests), [V1_ID, V1_ID]);
+        assert!(!state.lock().unwrap()[V1_ID]);
+        assert!(state.lock().unwrap()[other]);
+    }
+    Ok(())
+}